@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+#[cfg(feature = "toml")]
+use std::str::FromStr;
+
+#[cfg(feature = "toml")]
+use toml_edit::{Decor, DocumentMut, Item, RawString, TableLike};
+
+use crate::format::Format;
+#[cfg(feature = "json")]
+use crate::json5;
+use crate::options::LoadOptions;
+use crate::path::Path;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use crate::path::Segment;
+#[cfg(feature = "yaml")]
+use crate::yaml_loader;
+
+/// The comments around a value in the source document.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comment {
+    /// Whole-line comments above the value, one per line, without the `#`.
+    pub before: Vec<String>,
+    /// The comment at the end of the value's line.
+    pub after: Option<String>,
+}
+
+/// Comments by the path of the value they belong to.
+pub type Comments = HashMap<Path, Comment>;
+
+/// Reads the comments of each document in `text`.
+///
+/// Only TOML, YAML, JSONC and JSON5 have comments to read. Text that does not parse has
+/// none, so this can be called on a document that was already parsed
+/// without handling the same errors again.
+#[cfg_attr(not(feature = "yaml"), allow(unused_variables))]
+pub fn read(text: &str, format: Format, yaml: &LoadOptions) -> Vec<Comments> {
+    match format {
+        #[cfg(feature = "toml")]
+        Format::Toml => vec![read_toml(text)],
+        #[cfg(feature = "yaml")]
+        Format::Yaml => read_yaml(text, yaml),
+        #[cfg(feature = "json")]
+        Format::Jsonc | Format::Json5 => match json5::parse(text, format == Format::Json5) {
+            Ok((_, comments)) => vec![comments],
+            Err(_) => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn add(out: &mut Comments, path: &Path, before: Vec<String>, after: Option<String>) {
+    if before.is_empty() && after.is_none() {
+        return;
+    }
+    let comment = out.entry(path.clone()).or_default();
+    comment.before.extend(before);
+    if after.is_some() {
+        comment.after = after;
+    }
+}
+
+/// The text of a `#` comment, which may have whitespace around it.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn comment_text(s: &str) -> Option<String> {
+    let text = s.trim().strip_prefix('#')?;
+    Some(text.strip_prefix(' ').unwrap_or(text).trim_end().to_owned())
+}
+
+/// The comments in `s`, which is made of whole lines.
+#[cfg(feature = "toml")]
+fn comment_lines(s: &str) -> Vec<String> {
+    s.lines().filter_map(comment_text).collect()
+}
+
+/***********************************************/
+// TOML
+
+#[cfg(feature = "toml")]
+fn raw(s: Option<&RawString>) -> &str {
+    s.and_then(RawString::as_str).unwrap_or("")
+}
+
+#[cfg(feature = "toml")]
+fn read_toml(text: &str) -> Comments {
+    let mut out = Comments::new();
+    if let Ok(doc) = DocumentMut::from_str(text) {
+        read_toml_table(doc.as_table(), &mut Path::new(), &mut out);
+    }
+    out
+}
+
+#[cfg(feature = "toml")]
+fn read_toml_table(table: &dyn TableLike, path: &mut Path, out: &mut Comments) {
+    for (key, item) in table.iter() {
+        path.push(Segment::Key(key.to_owned()));
+        let key_prefix = table.key(key).map_or("", |k| raw(k.leaf_decor().prefix()));
+        match item {
+            Item::Value(val) => {
+                let after = comment_text(raw(val.decor().suffix()));
+                add(out, path, comment_lines(key_prefix), after);
+                read_toml_value(val, path, out);
+            }
+            // `a.b = 1` puts `a` in a table without a header of its own.
+            Item::Table(t) if t.is_dotted() => {
+                add(out, path, comment_lines(key_prefix), None);
+                read_toml_table(t, path, out);
+            }
+            Item::Table(t) => {
+                read_toml_header(t.decor(), path, out);
+                read_toml_table(t, path, out);
+            }
+            Item::ArrayOfTables(tables) => {
+                for (i, t) in tables.iter().enumerate() {
+                    path.push(Segment::Index(i));
+                    read_toml_header(t.decor(), path, out);
+                    read_toml_table(t, path, out);
+                    path.pop();
+                }
+            }
+            Item::None => {}
+        }
+        path.pop();
+    }
+}
+
+#[cfg(feature = "toml")]
+fn read_toml_header(decor: &Decor, path: &Path, out: &mut Comments) {
+    let after = comment_text(raw(decor.suffix()));
+    add(out, path, comment_lines(raw(decor.prefix())), after);
+}
+
+#[cfg(feature = "toml")]
+fn read_toml_value(val: &toml_edit::Value, path: &mut Path, out: &mut Comments) {
+    match val {
+        toml_edit::Value::Array(array) => {
+            let len = array.len();
+            for (i, item) in array.iter().enumerate() {
+                // Whatever follows the comma of the previous element on its
+                // line is in the prefix of this one.
+                let prefix = raw(item.decor().prefix());
+                let before = match prefix.split_once('\n') {
+                    Some((rest_of_line, lines)) if i > 0 => {
+                        path.push(Segment::Index(i - 1));
+                        add(out, path, vec![], comment_text(rest_of_line));
+                        path.pop();
+                        comment_lines(lines)
+                    }
+                    _ => comment_lines(prefix),
+                };
+                path.push(Segment::Index(i));
+                add(out, path, before, comment_text(raw(item.decor().suffix())));
+                read_toml_value(item, path, out);
+                path.pop();
+            }
+            let trailing = array.trailing().as_str().unwrap_or("");
+            if let (Some(last), Some((rest_of_line, _))) =
+                (len.checked_sub(1), trailing.split_once('\n'))
+            {
+                path.push(Segment::Index(last));
+                add(out, path, vec![], comment_text(rest_of_line));
+                path.pop();
+            }
+        }
+        toml_edit::Value::InlineTable(t) => read_toml_table(t, path, out),
+        _ => {}
+    }
+}
+
+/***********************************************/
+// YAML
+
+/// A line of YAML source, as far as comments go.
+#[cfg(feature = "yaml")]
+struct SourceLine {
+    /// Whether the line holds anything but whitespace and a comment.
+    content: bool,
+    comment: Option<String>,
+}
+
+#[cfg(feature = "yaml")]
+fn read_yaml(text: &str, options: &LoadOptions) -> Vec<Comments> {
+    let Ok((_, lines)) = yaml_loader::load_with_lines(text, options) else {
+        return vec![];
+    };
+    let source = scan_yaml(text);
+    lines
+        .into_iter()
+        .map(|doc| assign_yaml(&source, doc))
+        .collect()
+}
+
+/// Gives each value the comment lines right above its line, and the comment
+/// at the end of its line.
+///
+/// Several values can start on the same line, as in `- name: web`. The
+/// first, outermost one gets the comments above, and the last key on the
+/// line gets the one after.
+#[cfg(feature = "yaml")]
+fn assign_yaml(source: &[SourceLine], mut lines: Vec<(Path, usize)>) -> Comments {
+    let mut out = Comments::new();
+    lines.sort_by_key(|(_, line)| *line);
+    let mut lines = lines.into_iter().peekable();
+    while let Some((first, line)) = lines.next() {
+        let mut on_line = vec![first];
+        while let Some((path, _)) = lines.next_if(|(_, l)| *l == line) {
+            on_line.push(path);
+        }
+        // Lines count from 1.
+        let index = line.saturating_sub(1);
+        let mut before: Vec<_> = source[..index.min(source.len())]
+            .iter()
+            .rev()
+            .take_while(|l| !l.content)
+            .filter_map(|l| l.comment.clone())
+            .collect();
+        before.reverse();
+        add(&mut out, &on_line[0], before, None);
+
+        let after = source.get(index).and_then(|l| l.comment.clone());
+        let last_key = on_line
+            .iter()
+            .rev()
+            .find(|path| matches!(path.segments().last(), Some(Segment::Key(_))))
+            .unwrap_or(&on_line[on_line.len() - 1]);
+        add(&mut out, last_key, vec![], after);
+    }
+    out
+}
+
+#[cfg(feature = "yaml")]
+fn scan_yaml(text: &str) -> Vec<SourceLine> {
+    let mut out = vec![];
+    // The indentation of the line that started the block scalar we are in.
+    let mut block: Option<usize> = None;
+    for line in text.lines() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let blank = line.trim().is_empty();
+        if let Some(parent) = block {
+            if blank || indent > parent {
+                out.push(SourceLine {
+                    content: !blank,
+                    comment: None,
+                });
+                continue;
+            }
+            block = None;
+        }
+        let (code, comment) = split_comment(line);
+        if starts_block_scalar(code.trim_end()) {
+            block = Some(indent);
+        }
+        out.push(SourceLine {
+            content: !code.trim().is_empty(),
+            comment,
+        });
+    }
+    out
+}
+
+/// Splits off a comment, which starts with a `#` after whitespace that is not
+/// inside quotes.
+#[cfg(feature = "yaml")]
+fn split_comment(line: &str) -> (&str, Option<String>) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '#' && prev.is_whitespace() => {
+                return (&line[..i], comment_text(&line[i..]))
+            }
+            // Quotes only start a scalar, `it's` is plain text.
+            None if (c == '"' || c == '\'') && (prev.is_whitespace() || "[{,:-".contains(prev)) => {
+                quote = Some(c)
+            }
+            None => {}
+        }
+        prev = c;
+    }
+    (line, None)
+}
+
+/// Whether a line ends in a `|` or `>` block scalar header, like `key: |-`.
+#[cfg(feature = "yaml")]
+fn starts_block_scalar(code: &str) -> bool {
+    let last = code.rsplit(' ').next().unwrap_or("");
+    let mut chars = last.chars();
+    matches!(chars.next(), Some('|' | '>'))
+        && chars.all(|c| c == '-' || c == '+' || c.is_ascii_digit())
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::table::Table;
+use crate::value::{Datetime, Value};
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::serde(msg)
+    }
+}
+
+/// Converts a [`Value`] or a [`Table`] into anything `Deserialize`, like a
+/// config struct.
+///
+/// Datetimes deserialize into `toml_datetime::Datetime`, which [`Datetime`]
+/// is, or into strings.
+pub fn from_value<T: DeserializeOwned>(value: impl Into<Value>) -> Result<T, Error> {
+    T::deserialize(value.into())
+}
+
+/// The struct name and the one field `Datetime` goes by in serde, which
+/// `toml` knows it by. `toml_datetime` keeps them private, so they are
+/// asked of its `Deserialize` implementation once.
+pub(crate) fn datetime_names() -> Option<(&'static str, &'static str)> {
+    static NAMES: OnceLock<Option<(&'static str, &'static str)>> = OnceLock::new();
+    *NAMES.get_or_init(|| match Datetime::deserialize(Probe) {
+        Err(Found(names)) => names,
+        Ok(_) => None,
+    })
+}
+
+/// A deserializer that only notes the names of the struct asked for.
+struct Probe;
+
+#[derive(Debug)]
+struct Found(Option<(&'static str, &'static str)>);
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not a struct")
+    }
+}
+
+impl std::error::Error for Found {}
+
+impl de::Error for Found {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Self(None)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Probe {
+    type Error = Found;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Found> {
+        Err(Found(None))
+    }
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Found> {
+        Err(Found(fields.first().map(|field| (name, *field))))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+fn unexpected(val: &Value) -> Unexpected<'_> {
+    match val {
+        Value::Null => Unexpected::Other("null"),
+        Value::Int(i) => Unexpected::Signed(*i),
+        Value::UInt(i) => Unexpected::Unsigned(*i),
+        Value::Float(f) => Unexpected::Float(*f),
+        Value::String(s) => Unexpected::Str(s),
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::Datetime(_) => Unexpected::Other("datetime"),
+        Value::Array(_) => Unexpected::Seq,
+        Value::Table(_) => Unexpected::Map,
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Table {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Table(t) => Ok(t),
+            val => Err(de::Error::invalid_type(unexpected(&val), &"a table")),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+    /// Like the parsers, keeps integers that are not negative as `UInt`.
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(match u64::try_from(v) {
+            Ok(u) => Value::UInt(u),
+            Err(_) => Value::Int(v),
+        })
+    }
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        match (i64::try_from(v), u64::try_from(v)) {
+            (Ok(i), _) => self.visit_i64(i),
+            (_, Ok(u)) => self.visit_u64(u),
+            _ => Err(E::custom(format!("integer {v} is out of range"))),
+        }
+    }
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::UInt(v))
+    }
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Value, E> {
+        match u64::try_from(v) {
+            Ok(u) => self.visit_u64(u),
+            Err(_) => Err(E::custom(format!("integer {v} is out of range"))),
+        }
+    }
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Array(
+            v.iter().map(|&b| Value::UInt(b.into())).collect(),
+        ))
+    }
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(val) = seq.next_element()? {
+            items.push(val);
+        }
+        Ok(Value::Array(items))
+    }
+    /// Deserializers with a datetime type, like the `toml` crate's, hand it
+    /// over as a map with a single, private key.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut table = Table::with_capacity(map.size_hint().unwrap_or(0));
+        let Some(key) = map.next_key::<String>()? else {
+            return Ok(Value::Table(table));
+        };
+        if datetime_names().is_some_and(|(_, field)| key == field) {
+            let text = map.next_value::<String>()?;
+            return text
+                .parse::<Datetime>()
+                .map(Value::Datetime)
+                .map_err(de::Error::custom);
+        }
+        table.items.insert(key, map.next_value()?);
+        while let Some((key, val)) = map.next_entry()? {
+            table.items.insert(key, val);
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+impl Value {
+    fn visit_array<'de, V: Visitor<'de>>(items: Vec<Value>, visitor: V) -> Result<V::Value, Error> {
+        let mut seq = SeqDeserializer::<_, Error>::new(items.into_iter());
+        let out = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(out)
+    }
+    fn visit_table<'de, V: Visitor<'de>>(table: Table, visitor: V) -> Result<V::Value, Error> {
+        let mut map = MapDeserializer::<_, Error>::new(table.items.into_iter());
+        let out = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(out)
+    }
+}
+
+/// A datetime deserializes as the struct `toml_datetime::Datetime` expects,
+/// or as a string when one is asked for.
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Self::Null => visitor.visit_unit(),
+            Self::Int(i) => visitor.visit_i64(i),
+            Self::UInt(i) => visitor.visit_u64(i),
+            Self::Float(f) => visitor.visit_f64(f),
+            Self::String(s) => visitor.visit_string(s),
+            Self::Bool(b) => visitor.visit_bool(b),
+            Self::Datetime(dt) => {
+                let Some((_, field)) = datetime_names() else {
+                    return visitor.visit_string(dt.to_string());
+                };
+                let mut map =
+                    MapDeserializer::<_, Error>::new(std::iter::once((field, dt.to_string())));
+                let out = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(out)
+            }
+            Self::Array(a) => Self::visit_array(a, visitor),
+            Self::Table(t) => Self::visit_table(t, visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Self::Datetime(dt) => visitor.visit_string(dt.to_string()),
+            val => val.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Self::Null => visitor.visit_none(),
+            val => visitor.visit_some(val),
+        }
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    /// Unit variants are strings, and variants with data tables with the
+    /// variant name as their one key, as [`to_value`](crate::to_value)
+    /// writes them.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Self::String(s) => visitor.visit_enum(s.into_deserializer()),
+            Self::Table(t) if t.items.len() == 1 => {
+                let (name, value) = t.items.into_iter().next().expect("one item");
+                visitor.visit_enum(Variant { name, value })
+            }
+            val => Err(de::Error::invalid_type(
+                unexpected(&val),
+                &"a string or a table with one key",
+            )),
+        }
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// An enum variant with data, read from a table with one key.
+struct Variant {
+    name: String,
+    value: Value,
+}
+
+impl<'de> EnumAccess<'de> for Variant {
+    type Error = Error;
+    type Variant = Value;
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Value), Error> {
+        let name: StringDeserializer<Error> = self.name.into_deserializer();
+        Ok((seed.deserialize(name)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Value {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        <()>::deserialize(self)
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
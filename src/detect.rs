@@ -0,0 +1,251 @@
+use std::path::Path;
+#[cfg(any(feature = "json", feature = "toml"))]
+use std::str::FromStr;
+
+#[cfg(feature = "yaml")]
+use yaml_rust::Yaml;
+
+use crate::format::Format;
+#[cfg(feature = "json")]
+use crate::json5;
+#[cfg(feature = "yaml")]
+use crate::options::LoadOptions;
+use crate::registry;
+use crate::value::Value;
+#[cfg(feature = "yaml")]
+use crate::yaml_loader;
+
+/// How sure [`detect`] is about the format it picked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Only parsed as a bare YAML scalar, as INI without a `[section]`, or
+    /// as a registered format, or the input was empty.
+    Low,
+    /// Parsed as more than one format; the first one in sniffing order won.
+    Medium,
+    /// Exactly one format parsed into a structured document.
+    High,
+    /// Taken from the file extension.
+    Certain,
+}
+
+/// A document parsed while sniffing, ready for `Table::from_*` or `Stream::from_*`.
+/// Which variants exist depends on the cargo features enabled.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Parsed {
+    #[cfg(feature = "json")]
+    Json(serde_json::Value),
+    #[cfg(feature = "json")]
+    JsonLines(Vec<serde_json::Value>),
+    /// JSONC or JSON5, which are read straight into a `Value`.
+    #[cfg(feature = "json")]
+    Json5(Value),
+    #[cfg(feature = "toml")]
+    Toml(toml::Table),
+    #[cfg(feature = "yaml")]
+    Yaml(Vec<Yaml>),
+    #[cfg(feature = "ini")]
+    Ini(ini::Ini),
+    /// A registered format, which reads straight into a `Value`.
+    Custom(Value),
+}
+
+#[derive(Debug)]
+pub struct Detection {
+    pub format: Format,
+    pub confidence: Confidence,
+    /// Present when the format was found by parsing the content.
+    pub parsed: Option<Parsed>,
+}
+
+impl Format {
+    /// The built-in or registered format with the extension of `path`.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        registry::formats()
+            .into_iter()
+            .find(|f| f.extensions().contains(&ext.as_str()))
+    }
+}
+
+/// Picks the format of `content`, preferring the extension of `path` when it has a known one.
+pub fn detect(path: Option<&Path>, content: &str) -> Option<Detection> {
+    match path.and_then(Format::from_extension) {
+        Some(format) => Some(Detection {
+            format,
+            confidence: Confidence::Certain,
+            parsed: None,
+        }),
+        None => sniff(content),
+    }
+}
+
+/// Guesses the format by trying each parser, strictest first: JSON, JSON Lines,
+/// JSONC, JSON5, TOML, YAML, then INI.
+///
+/// JSON is a subset of YAML, so a JSON hit is never ambiguous. JSONC and JSON5
+/// objects without comments can also be YAML flow mappings, which makes them
+/// `Medium`. TOML and YAML
+/// rarely overlap, but when both produce a mapping the guess is only `Medium`.
+/// INI accepts nearly anything with an `=` or `:` in it, broken JSON and YAML
+/// included, so it only wins over YAML when YAML did not find any structure,
+/// is never tried on text that starts like a JSON object or array, and is
+/// only `High` with a `[section]` header.
+///
+/// Registered formats are tried after INI, in the order they were added, and
+/// only make a `Low` guess. Formats left out by cargo features are not tried.
+pub fn sniff(content: &str) -> Option<Detection> {
+    #[cfg(feature = "yaml")]
+    let yaml = yaml_loader::load(content, &LoadOptions::default())
+        .ok()
+        .filter(|docs| !docs.is_empty());
+    #[cfg(feature = "yaml")]
+    #[cfg_attr(not(feature = "toml"), allow(unused_variables))]
+    let (yaml_is_structured, yaml_is_mapping) = match yaml.as_ref().map(|docs| &docs[0]) {
+        Some(Yaml::Hash(_)) => (true, true),
+        Some(Yaml::Array(_)) => (true, false),
+        _ => (false, false),
+    };
+    #[cfg(not(feature = "yaml"))]
+    #[cfg_attr(not(all(feature = "json", feature = "toml")), allow(unused_variables))]
+    let (yaml_is_structured, yaml_is_mapping) = (false, false);
+    let blank = content.trim().is_empty();
+
+    #[cfg(feature = "json")]
+    if let Ok(json) = serde_json::Value::from_str(content) {
+        return Some(Detection {
+            format: Format::Json,
+            confidence: Confidence::High,
+            parsed: Some(Parsed::Json(json)),
+        });
+    }
+
+    #[cfg(feature = "json")]
+    let lines = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::Value::from_str)
+        .collect::<Result<Vec<_>, _>>();
+    #[cfg(feature = "json")]
+    if let Ok(lines) = lines {
+        if lines.len() > 1 && lines.iter().all(serde_json::Value::is_object) {
+            return Some(Detection {
+                format: Format::JsonLines,
+                confidence: Confidence::High,
+                parsed: Some(Parsed::JsonLines(lines)),
+            });
+        }
+    }
+
+    // JSONC and JSON5 documents of interest are objects, and as lenient as
+    // JSON5 is, it takes little else that starts with a brace.
+    #[cfg(feature = "json")]
+    if content.trim_start().starts_with('{') {
+        for (format, json5) in [(Format::Jsonc, false), (Format::Json5, true)] {
+            if let Ok((value, _)) = json5::parse(content, json5) {
+                return Some(Detection {
+                    format,
+                    confidence: match yaml_is_structured {
+                        true => Confidence::Medium,
+                        false => Confidence::High,
+                    },
+                    parsed: Some(Parsed::Json5(value)),
+                });
+            }
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    if let Ok(toml) = toml::Table::from_str(content) {
+        let confidence = if blank {
+            Confidence::Low
+        } else if yaml_is_mapping {
+            Confidence::Medium
+        } else {
+            Confidence::High
+        };
+        return Some(Detection {
+            format: Format::Toml,
+            confidence,
+            parsed: Some(Parsed::Toml(toml)),
+        });
+    }
+
+    #[cfg(feature = "yaml")]
+    if yaml_is_structured {
+        return yaml.map(|yaml| Detection {
+            format: Format::Yaml,
+            confidence: Confidence::High,
+            parsed: Some(Parsed::Yaml(yaml)),
+        });
+    }
+
+    #[cfg(feature = "ini")]
+    if let Some(Ok(ini)) = (!starts_like_json(content)).then(|| ini::Ini::load_from_str(content)) {
+        if ini
+            .iter()
+            .any(|(section, props)| section.is_some() || !props.is_empty())
+        {
+            let sections = ini.sections().any(|section| section.is_some());
+            return Some(Detection {
+                format: Format::Ini,
+                confidence: match sections {
+                    true => Confidence::High,
+                    false => Confidence::Low,
+                },
+                parsed: Some(Parsed::Ini(ini)),
+            });
+        }
+    }
+
+    let registered = registry::formats()
+        .into_iter()
+        .filter(|_| !blank)
+        .filter_map(|format| match format {
+            Format::Custom(name) => Some((format, registry::get(name)?)),
+            #[cfg_attr(
+                not(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini")),
+                allow(unreachable_patterns)
+            )]
+            _ => None,
+        });
+    for (format, custom) in registered {
+        if let Ok(value) = custom.parse(content.as_bytes()) {
+            return Some(Detection {
+                format,
+                confidence: Confidence::Low,
+                parsed: Some(Parsed::Custom(value)),
+            });
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    if let Some(yaml) = yaml {
+        return Some(Detection {
+            format: Format::Yaml,
+            confidence: Confidence::Low,
+            parsed: Some(Parsed::Yaml(yaml)),
+        });
+    }
+    None
+}
+
+/// Whether `content` starts with `{`, or with a `[` that does not open an
+/// INI `[section]` header on its first line.
+#[cfg(feature = "ini")]
+fn starts_like_json(content: &str) -> bool {
+    let first = content
+        .trim_start()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end();
+    match first
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        Some(name) => name.trim().is_empty() || name.contains(['[', ']', '{', '}', '"', ',']),
+        None => first.starts_with(['{', '[']),
+    }
+}
@@ -0,0 +1,195 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::format::Format;
+use crate::parse::ParseError;
+use crate::path::Path;
+#[cfg(any(feature = "yaml", feature = "ini"))]
+use crate::path::Segment;
+use crate::report::ConversionReport;
+
+/// Everything that can go wrong reading, converting or writing a document.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The input is not valid in its format.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// The input parsed, but holds a value that can not be read, like a
+    /// malformed YAML float or keys that collide once stringified.
+    #[error("{}Invalid {format} value: {message}", at(path))]
+    Invalid {
+        format: Format,
+        path: Path,
+        message: String,
+    },
+    /// A value the target format has no exact way to write, with the
+    /// options set to fail rather than write it some other way.
+    #[error("{}{format} can not represent {what}", at(path))]
+    Unsupported {
+        format: Format,
+        path: Path,
+        what: Unsupported,
+    },
+    /// The conversion went through, but changed values the caller asked to
+    /// keep exact.
+    #[error(
+        "Converting to {format} would change {} value{}",
+        report.entries.len(),
+        if report.entries.len() == 1 { "" } else { "s" }
+    )]
+    Lossy {
+        format: Format,
+        report: ConversionReport,
+    },
+    /// The converted document could not be written out as text.
+    #[error("Failed to write {format}")]
+    Serialize {
+        format: Format,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("Top level of the {0} document is not a table")]
+    NotATable(Format),
+    #[error("Expected one {0} document, found {1}")]
+    DocumentCount(Format, usize),
+    /// A `Serialize` or `Deserialize` implementation failed in
+    /// [`to_value`](crate::to_value) or [`from_value`](crate::from_value).
+    #[error("{message}")]
+    Serde {
+        message: String,
+        /// The error behind the message, when there is one beyond serde's
+        /// own `custom` message.
+        #[source]
+        source: Option<Box<dyn StdError + Send + Sync>>,
+    },
+    /// A [`Format::Custom`] that was never
+    /// [registered](crate::registry::register).
+    #[error("No format named {0} is registered")]
+    Unregistered(&'static str),
+    /// Text that is not a [`Path`], like `servers[`.
+    #[error("Invalid path '{path}': {message}")]
+    InvalidPath { path: String, message: String },
+    /// A [`Table::set_path`](crate::Table::set_path) that runs into a value
+    /// that is not a table or an array, or past the end of an array.
+    #[error("Can not set {path}: {message}")]
+    SetPath { path: Path, message: String },
+    /// Text that is not a [`Query`](crate::Query).
+    #[error("Invalid query at column {column}: {message}")]
+    InvalidQuery { column: usize, message: String },
+    /// A [`Query`](crate::Query) that fails on its input, like `.a` on an
+    /// array.
+    #[error("{0}")]
+    QueryFailed(String),
+    /// An error in one document of a [`Stream`](crate::stream::Stream).
+    #[error("Document {index}")]
+    Document {
+        index: usize,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// The kinds of values some formats can not write.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unsupported {
+    Null,
+    NonFiniteFloat(f64),
+    /// An unsigned integer above `i64::MAX`.
+    IntegerOverflow(u64),
+    Array,
+    /// An array inside an array.
+    NestedArray,
+    /// A table inside a table that is itself not at the top level.
+    NestedTable,
+    TableInArray,
+    /// A key that would read back as another key, or not at all.
+    Key(&'static str),
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::NonFiniteFloat(x) => write!(f, "the non-finite float {x}"),
+            Self::IntegerOverflow(i) => write!(f, "{i}, which is above the signed 64-bit limit"),
+            Self::Array => f.write_str("arrays"),
+            Self::NestedArray => f.write_str("nested arrays"),
+            Self::NestedTable => f.write_str("nested tables"),
+            Self::TableInArray => f.write_str("tables in arrays"),
+            Self::Key(why) => f.write_str(why),
+        }
+    }
+}
+
+/// The `path: ` prefix of a message, left out at the root.
+fn at(path: &Path) -> String {
+    match path.is_root() {
+        true => String::new(),
+        false => format!("{path}: "),
+    }
+}
+
+impl Error {
+    #[cfg(any(feature = "json", feature = "yaml", feature = "ini"))]
+    pub(crate) fn invalid(format: Format, path: &Path, message: impl Into<String>) -> Self {
+        Self::Invalid {
+            format,
+            path: path.clone(),
+            message: message.into(),
+        }
+    }
+
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+    pub(crate) fn unsupported(format: Format, path: &Path, what: Unsupported) -> Self {
+        Self::Unsupported {
+            format,
+            path: path.clone(),
+            what,
+        }
+    }
+
+    pub(crate) fn serialize(format: Format, source: impl StdError + Send + Sync + 'static) -> Self {
+        Self::Serialize {
+            format,
+            source: Box::new(source),
+        }
+    }
+
+    /// An error of a `Serialize` or `Deserialize` implementation, which
+    /// serde only hands over as a message.
+    pub(crate) fn serde(message: impl fmt::Display) -> Self {
+        Self::Serde {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    /// Moves the error down into the value at `segment`, for errors that
+    /// pick up their path on the way back up from a nested value.
+    #[cfg(any(feature = "yaml", feature = "ini"))]
+    pub(crate) fn within(mut self, segment: Segment) -> Self {
+        if let Self::Invalid { path, .. } | Self::Unsupported { path, .. } = &mut self {
+            path.prepend(segment);
+        }
+        self
+    }
+
+    /// Wraps an error from the document at `index` of a stream.
+    pub fn in_document(self, index: usize) -> Self {
+        Self::Document {
+            index,
+            source: Box::new(self),
+        }
+    }
+
+    /// The path of the value the error is about, if it is about one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Invalid { path, .. } | Self::Unsupported { path, .. } => Some(path),
+            Self::Document { source, .. } => source.path(),
+            _ => None,
+        }
+    }
+}
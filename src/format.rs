@@ -0,0 +1,168 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::registry::{self, Capabilities};
+
+/// A format, built in or registered. The built-in ones depend on the cargo
+/// features enabled, so matches need a wildcard arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    /// One JSON document per line.
+    #[cfg(feature = "json")]
+    JsonLines,
+    /// JSON with comments and trailing commas.
+    #[cfg(feature = "json")]
+    Jsonc,
+    /// JSONC plus unquoted keys, single quotes, hex numbers, `Infinity` and
+    /// `NaN`.
+    #[cfg(feature = "json")]
+    Json5,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "ini")]
+    Ini,
+    /// A [`FileFormat`](registry::FileFormat) added with
+    /// [`registry::register`], by its name.
+    Custom(&'static str),
+}
+
+impl Format {
+    /// The formats built in with the enabled cargo features.
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            #[cfg(feature = "json")]
+            Self::Json,
+            #[cfg(feature = "json")]
+            Self::JsonLines,
+            #[cfg(feature = "json")]
+            Self::Jsonc,
+            #[cfg(feature = "json")]
+            Self::Json5,
+            #[cfg(feature = "toml")]
+            Self::Toml,
+            #[cfg(feature = "yaml")]
+            Self::Yaml,
+            #[cfg(feature = "ini")]
+            Self::Ini,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => "json",
+            #[cfg(feature = "json")]
+            Self::JsonLines => "jsonl",
+            #[cfg(feature = "json")]
+            Self::Jsonc => "jsonc",
+            #[cfg(feature = "json")]
+            Self::Json5 => "json5",
+            #[cfg(feature = "toml")]
+            Self::Toml => "toml",
+            #[cfg(feature = "yaml")]
+            Self::Yaml => "yaml",
+            #[cfg(feature = "ini")]
+            Self::Ini => "ini",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// File extensions of the format, without the dot. Besides the name,
+    /// these are what [`Format::from_str`] takes.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => &["json"],
+            #[cfg(feature = "json")]
+            Self::JsonLines => &["jsonl", "ndjson"],
+            #[cfg(feature = "json")]
+            Self::Jsonc => &["jsonc"],
+            #[cfg(feature = "json")]
+            Self::Json5 => &["json5"],
+            #[cfg(feature = "toml")]
+            Self::Toml => &["toml"],
+            #[cfg(feature = "yaml")]
+            Self::Yaml => &["yaml", "yml"],
+            #[cfg(feature = "ini")]
+            Self::Ini => &["ini"],
+            Self::Custom(name) => registry::get(name).map_or(&[], |f| f.extensions()),
+        }
+    }
+
+    pub fn capabilities(self) -> Capabilities {
+        #[cfg_attr(
+            not(any(feature = "json", feature = "toml", feature = "yaml")),
+            allow(unused_variables)
+        )]
+        let json = Capabilities {
+            null: true,
+            nesting: true,
+            ..Capabilities::default()
+        };
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json => json,
+            #[cfg(feature = "json")]
+            Self::JsonLines => Capabilities {
+                multiple_documents: true,
+                ..json
+            },
+            #[cfg(feature = "json")]
+            Self::Jsonc => Capabilities {
+                comments: true,
+                ..json
+            },
+            #[cfg(feature = "json")]
+            Self::Json5 => Capabilities {
+                comments: true,
+                non_finite_floats: true,
+                ..json
+            },
+            #[cfg(feature = "toml")]
+            Self::Toml => Capabilities {
+                null: false,
+                datetimes: true,
+                non_finite_floats: true,
+                comments: true,
+                ..json
+            },
+            #[cfg(feature = "yaml")]
+            Self::Yaml => Capabilities {
+                datetimes: true,
+                non_finite_floats: true,
+                comments: true,
+                multiple_documents: true,
+                ..json
+            },
+            #[cfg(feature = "ini")]
+            Self::Ini => Capabilities::default(),
+            Self::Custom(name) => {
+                registry::get(name).map_or_else(Capabilities::default, |f| f.capabilities())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Takes the name or one of the extensions of a built-in or registered
+/// format, in any case.
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        registry::formats()
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s) || f.extensions().contains(&lower.as_str()))
+            .ok_or_else(|| format!("unknown format '{s}'"))
+    }
+}
@@ -0,0 +1,835 @@
+use crate::comment::{Comment, Comments};
+use crate::error::{Error, Unsupported};
+use crate::format::Format;
+use crate::options::{NonFinitePolicy, Options};
+use crate::path::{Path, Segment};
+use crate::report::{ConversionReport, Ctx, LossKind};
+use crate::style::{KeyQuotes, StringQuotes, StyleOptions};
+use crate::table::Table;
+use crate::value::{non_finite_name, Value};
+
+/// Where and why a document failed to parse.
+#[derive(Debug, thiserror::Error)]
+#[error("{message} at line {line} column {column}")]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Parses a JSON5 document, or a JSONC one if `json5` is false, along with
+/// its comments. `serde_json` reads neither, so this goes straight to a
+/// [`Value`].
+///
+/// JSONC is JSON with `//` and `/* */` comments and trailing commas. JSON5
+/// adds unquoted keys, single-quoted strings, hex numbers, `Infinity` and
+/// `NaN`, and a few more number and string spellings.
+pub fn parse(text: &str, json5: bool) -> Result<(Value, Comments), SyntaxError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+        json5,
+        path: Path::new(),
+        comments: Comments::new(),
+        pending: vec![],
+        last: None,
+        depth: 0,
+    };
+    parser.skip()?;
+    let val = parser.value()?;
+    parser.skip()?;
+    match parser.peek() {
+        Some(_) => Err(parser.error("trailing characters after the document")),
+        None => Ok((val, parser.comments)),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    json5: bool,
+    /// The path of the value being parsed.
+    path: Path,
+    comments: Comments,
+    /// Comment lines waiting for the next key or array element.
+    pending: Vec<String>,
+    /// The value that last ended or opened, and the line it did so on. A
+    /// comment later on that line is about it.
+    last: Option<(Path, usize)>,
+    /// How many objects and arrays the parser is inside of.
+    depth: usize,
+}
+
+/// How deep objects and arrays may nest, as in `serde_json`, so that deeply
+/// nested input fails instead of overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SyntaxError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.unexpected(&format!("'{c}'"))),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> SyntaxError {
+        match self.peek() {
+            Some(c) => self.error(format!("expected {expected}, found {c:?}")),
+            None => self.error(format!("expected {expected}, found the end of the input")),
+        }
+    }
+
+    /// Rejects a JSON5 feature in JSONC.
+    fn json5_only(&self, what: &str) -> Result<(), SyntaxError> {
+        match self.json5 {
+            true => Ok(()),
+            false => Err(self.error(format!("{what} are JSON5, not JSONC"))),
+        }
+    }
+
+    /// Skips whitespace and comments, keeping the comments.
+    fn skip(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() || c == '\u{feff}' => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    let line = self.line;
+                    let mut text = String::new();
+                    while let Some(c) = self.peek().filter(|c| *c != '\n') {
+                        text.push(c);
+                        self.bump();
+                    }
+                    self.comment(line, vec![text[2..].trim().to_owned()]);
+                }
+                (Some('/'), Some('*')) => {
+                    let line = self.line;
+                    self.bump();
+                    self.bump();
+                    let mut text = String::new();
+                    loop {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some('*'), Some('/')) => break,
+                            (Some(c), _) => text.push(c),
+                            (None, _) => return Err(self.error("unterminated block comment")),
+                        }
+                        self.bump();
+                    }
+                    self.bump();
+                    self.bump();
+                    let lines = text
+                        .lines()
+                        .map(|l| {
+                            let l = l.trim();
+                            l.strip_prefix('*').unwrap_or(l).trim().to_owned()
+                        })
+                        .skip_while(String::is_empty)
+                        .collect::<Vec<_>>();
+                    let end = lines
+                        .iter()
+                        .rposition(|l| !l.is_empty())
+                        .map_or(0, |i| i + 1);
+                    self.comment(line, lines[..end].to_vec());
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn comment(&mut self, line: usize, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        match &self.last {
+            Some((path, last_line)) if *last_line == line => {
+                let comment = self.comments.entry(path.clone()).or_default();
+                comment.after = Some(lines.join(" "));
+            }
+            _ => self.pending.extend(lines),
+        }
+    }
+
+    /// Gives the pending comments to the value at the current path.
+    fn begin(&mut self) {
+        if !self.pending.is_empty() {
+            let before = std::mem::take(&mut self.pending);
+            self.comments
+                .entry(self.path.clone())
+                .or_default()
+                .before
+                .extend(before);
+        }
+    }
+
+    fn end(&mut self) {
+        self.last = Some((self.path.clone(), self.line));
+    }
+
+    fn value(&mut self) -> Result<Value, SyntaxError> {
+        let val = match self.peek() {
+            Some('{' | '[') if self.depth == MAX_DEPTH => {
+                return Err(self.error(format!("nesting deeper than {MAX_DEPTH} levels")))
+            }
+            Some('{') => return self.nested(Self::object),
+            Some('[') => return self.nested(Self::array),
+            Some('"') => Value::String(self.string()?),
+            Some('\'') => {
+                self.json5_only("single-quoted strings")?;
+                Value::String(self.string()?)
+            }
+            Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'I' | 'N') => {
+                self.number()?
+            }
+            Some(c) if is_ident_start(c) => match self.ident().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                word => return Err(self.error(format!("unexpected word '{word}'"))),
+            },
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.end();
+        Ok(val)
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, SyntaxError>,
+    ) -> Result<Value, SyntaxError> {
+        self.depth += 1;
+        let val = parse(self);
+        self.depth -= 1;
+        val
+    }
+
+    fn object(&mut self) -> Result<Value, SyntaxError> {
+        self.bump();
+        self.end();
+        let mut table = Table::default();
+        loop {
+            self.skip()?;
+            if self.eat('}') {
+                break;
+            }
+            let key = self.key()?;
+            self.path.push(Segment::Key(key.clone()));
+            self.begin();
+            self.skip()?;
+            self.expect(':')?;
+            self.skip()?;
+            let val = self.value()?;
+            self.path.pop();
+            table.items.insert(key, val);
+            self.skip()?;
+            if !self.eat(',') {
+                self.skip()?;
+                if !self.eat('}') {
+                    return Err(self.unexpected("',' or '}'"));
+                }
+                break;
+            }
+        }
+        // Comments before the closing brace belong to nothing.
+        self.pending.clear();
+        self.end();
+        Ok(Value::Table(table))
+    }
+
+    fn array(&mut self) -> Result<Value, SyntaxError> {
+        self.bump();
+        self.end();
+        let mut array = vec![];
+        loop {
+            self.skip()?;
+            if self.eat(']') {
+                break;
+            }
+            self.path.push(Segment::Index(array.len()));
+            self.begin();
+            let val = self.value()?;
+            self.path.pop();
+            array.push(val);
+            self.skip()?;
+            if !self.eat(',') {
+                self.skip()?;
+                if !self.eat(']') {
+                    return Err(self.unexpected("',' or ']'"));
+                }
+                break;
+            }
+        }
+        self.pending.clear();
+        self.end();
+        Ok(Value::Array(array))
+    }
+
+    fn key(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some('"') => self.string(),
+            Some('\'') => {
+                self.json5_only("single-quoted strings")?;
+                self.string()
+            }
+            Some(c) if is_ident_start(c) => {
+                self.json5_only("unquoted keys")?;
+                Ok(self.ident())
+            }
+            _ => Err(self.unexpected("a key")),
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.peek().filter(|c| is_ident_char(*c)) {
+            out.push(c);
+            self.bump();
+        }
+        out
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        let quote = self.bump();
+        let mut out = String::new();
+        loop {
+            let c = match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if Some(c) == quote => return Ok(out),
+                Some('\\') => match self.bump() {
+                    None => return Err(self.error("unterminated string")),
+                    Some(c) => match self.escape(c)? {
+                        Some(c) => c,
+                        None => continue,
+                    },
+                },
+                Some(c) => c,
+            };
+            out.push(c);
+        }
+    }
+
+    /// The character an escape stands for, or `None` for a JSON5 line
+    /// continuation.
+    fn escape(&mut self, c: char) -> Result<Option<char>, SyntaxError> {
+        Ok(Some(match c {
+            '"' | '\\' | '/' => c,
+            'b' => '\x08',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.hex(4)?;
+                match (high, self.low_surrogate()) {
+                    (0xd800..=0xdbff, Some(low)) => {
+                        for _ in 0..6 {
+                            self.bump();
+                        }
+                        let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                        char::from_u32(code).unwrap_or('\u{fffd}')
+                    }
+                    // A lone surrogate, or a high one followed by anything but
+                    // a low one, which is then read on its own.
+                    _ => char::from_u32(high).unwrap_or('\u{fffd}'),
+                }
+            }
+            _ if !self.json5 => return Err(self.error(format!("invalid escape '\\{c}'"))),
+            'v' => '\x0b',
+            '0' => '\0',
+            'x' => char::from_u32(self.hex(2)?).unwrap_or('\u{fffd}'),
+            '\n' | '\u{2028}' | '\u{2029}' => return Ok(None),
+            '\r' => {
+                self.eat('\n');
+                return Ok(None);
+            }
+            c if c.is_ascii_digit() => return Err(self.error(format!("invalid escape '\\{c}'"))),
+            c => c,
+        }))
+    }
+
+    /// The code of a `\uDC00` to `\uDFFF` escape coming up next, without
+    /// reading it.
+    fn low_surrogate(&self) -> Option<u32> {
+        if self.peek() != Some('\\') || self.peek_at(1) != Some('u') {
+            return None;
+        }
+        let hex: String = (2..6).filter_map(|i| self.peek_at(i)).collect();
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .filter(|code| hex.len() == 4 && (0xdc00..=0xdfff).contains(code))
+    }
+
+    fn hex(&mut self, digits: usize) -> Result<u32, SyntaxError> {
+        let mut out = 0;
+        for _ in 0..digits {
+            match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(d) => out = out * 16 + d,
+                None => return Err(self.unexpected("a hex digit")),
+            }
+            self.bump();
+        }
+        Ok(out)
+    }
+
+    fn number(&mut self) -> Result<Value, SyntaxError> {
+        let negative = match self.peek() {
+            Some('-') => {
+                self.bump();
+                true
+            }
+            Some('+') => {
+                self.json5_only("leading '+' signs")?;
+                self.bump();
+                false
+            }
+            _ => false,
+        };
+        let sign = if negative { -1.0 } else { 1.0 };
+        if matches!(self.peek(), Some('I' | 'N')) {
+            self.json5_only("Infinity and NaN")?;
+            return match self.ident().as_str() {
+                "Infinity" => Ok(Value::Float(sign * f64::INFINITY)),
+                "NaN" => Ok(Value::Float(f64::NAN)),
+                word => Err(self.error(format!("unexpected word '{word}'"))),
+            };
+        }
+        if self.peek() == Some('0') && matches!(self.peek_at(1), Some('x' | 'X')) {
+            self.json5_only("hex numbers")?;
+            self.bump();
+            self.bump();
+            let mut digits = String::new();
+            while let Some(c) = self.peek().filter(char::is_ascii_hexdigit) {
+                digits.push(c);
+                self.bump();
+            }
+            let n = u64::from_str_radix(&digits, 16)
+                .map_err(|_| self.error(format!("invalid hex number '0x{digits}'")))?;
+            return Ok(match negative {
+                false => Value::UInt(n),
+                true => match i64::try_from(n) {
+                    Ok(i) => Value::Int(-i),
+                    Err(_) => Value::Float(-(n as f64)),
+                },
+            });
+        }
+
+        let mut text = String::from(if negative { "-" } else { "" });
+        let mut float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' => {}
+                '.' | 'e' | 'E' => float = true,
+                '+' | '-' if text.ends_with(['e', 'E']) => {}
+                _ => break,
+            }
+            text.push(c);
+            self.bump();
+        }
+        let digits = text.trim_start_matches('-');
+        if digits.starts_with('.') || digits.ends_with('.') || digits.contains(".e") {
+            self.json5_only("numbers with a leading or trailing '.'")?;
+        }
+        if !float {
+            if let Ok(i) = text.parse::<u64>() {
+                return Ok(Value::UInt(i));
+            }
+            if let Ok(i) = text.parse::<i64>() {
+                return Ok(Value::Int(i));
+            }
+        }
+        text.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| self.error(format!("invalid number '{text}'")))
+    }
+}
+
+/// Writes `val` as JSON, or as JSONC or JSON5 with `comments` as `//`
+/// comments, laid out as `style` asks.
+///
+/// Datetimes become strings in all three. JSON5 writes non-finite floats as
+/// `Infinity` and `NaN`; JSON and JSONC follow the [`NonFinitePolicy`].
+pub fn write(
+    val: &Value,
+    format: Format,
+    options: &Options,
+    style: &StyleOptions,
+    comments: &Comments,
+) -> Result<(String, ConversionReport), Error> {
+    let mut writer = Writer {
+        out: String::new(),
+        format,
+        style,
+        indent: style.indent_or(2),
+        comments,
+        flat: false,
+        ctx: Ctx::new(options),
+    };
+    writer.member(val, 0, false)?;
+    Ok((writer.out, writer.ctx.report))
+}
+
+struct Writer<'a> {
+    out: String,
+    format: Format,
+    style: &'a StyleOptions,
+    indent: String,
+    comments: &'a Comments,
+    /// Whether the collection being written goes on one line.
+    flat: bool,
+    ctx: Ctx<'a>,
+}
+
+impl Writer<'_> {
+    fn comment(&self) -> Option<&Comment> {
+        self.comments.get(&self.ctx.path)
+    }
+
+    /// Whether comments can be written here: not in JSON, and not on one
+    /// line, which `//` comments would end.
+    fn keeps_comments(&self) -> bool {
+        self.format != Format::Json && !self.flat && !self.style.compact
+    }
+
+    /// Whether anything inside the current value has a comment to write.
+    fn comments_inside(&self) -> bool {
+        let depth = self.ctx.path.segments().len();
+        self.format != Format::Json
+            && self
+                .comments
+                .keys()
+                .any(|path| path.segments().len() > depth && path.starts_with(&self.ctx.path))
+    }
+
+    fn indent(&mut self, level: usize) {
+        for _ in 0..level {
+            self.out.push_str(&self.indent);
+        }
+    }
+
+    fn column(&self) -> usize {
+        let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line..].chars().count()
+    }
+
+    /// Starts an entry of a table or array at the current path: on a line
+    /// of its own with the comments above it, or after the previous entry
+    /// in a collection on one line.
+    fn entry(&mut self, level: usize, first: bool) {
+        if !self.keeps_comments() && self.comment().is_some() {
+            self.ctx.lossy(LossKind::Dropped("comment"));
+        }
+        if self.flat {
+            match (first, self.style.compact) {
+                (true, _) => {}
+                (false, true) => self.out.push(','),
+                (false, false) => self.out.push_str(", "),
+            }
+            return;
+        }
+        self.out.push('\n');
+        if self.keeps_comments() {
+            self.write_before(level);
+        }
+        self.indent(level);
+    }
+
+    fn write_before(&mut self, level: usize) {
+        let comments = self.comments;
+        let Some(comment) = comments.get(&self.ctx.path) else {
+            return;
+        };
+        for line in &comment.before {
+            self.indent(level);
+            self.out.push_str("//");
+            if !line.is_empty() {
+                self.out.push(' ');
+                self.out.push_str(line);
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn write_after(&mut self) {
+        if !self.keeps_comments() {
+            return;
+        }
+        if let Some(after) = self.comment().and_then(|c| c.after.clone()) {
+            self.out.push_str(" //");
+            if !after.is_empty() {
+                self.out.push(' ');
+                self.out.push_str(&after);
+            }
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        if self.format != Format::Json5 || self.style.string_quotes != StringQuotes::Single {
+            self.out
+                .push_str(&serde_json::to_string(s).expect("strings always serialize"));
+            return;
+        }
+        self.out.push('\'');
+        for c in s.chars() {
+            match c {
+                '\'' => self.out.push_str("\\'"),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\0'..='\x1f' | '\u{2028}' | '\u{2029}' => {
+                    self.out.push_str(&format!("\\u{:04x}", c as u32))
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('\'');
+    }
+
+    /// JSON5 leaves keys that are identifiers unquoted.
+    fn key(&mut self, key: &str) {
+        let bare = self.format == Format::Json5
+            && self.style.key_quotes == KeyQuotes::Minimal
+            && key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+        match bare {
+            true => self.out.push_str(key),
+            false => self.string(key),
+        }
+        self.out.push(':');
+        if !self.style.compact {
+            self.out.push(' ');
+        }
+    }
+
+    fn table(&mut self, table: &Table, level: usize) -> Result<(), Error> {
+        self.out.push('{');
+        self.write_after();
+        for (i, (key, val)) in table.items.iter().enumerate() {
+            self.ctx.path.push(Segment::Key(key.clone()));
+            if table.typed_keys.contains_key(key) {
+                self.ctx.lossy(LossKind::KeyAsString);
+            }
+            self.entry(level + 1, i == 0);
+            self.key(key);
+            self.member(val, level + 1, i + 1 < table.items.len())?;
+            self.ctx.path.pop();
+        }
+        if !self.flat {
+            self.out.push('\n');
+            self.indent(level);
+        }
+        self.out.push('}');
+        Ok(())
+    }
+
+    fn array(&mut self, array: &[Value], level: usize) -> Result<(), Error> {
+        self.out.push('[');
+        self.write_after();
+        for (i, val) in array.iter().enumerate() {
+            self.ctx.path.push(Segment::Index(i));
+            self.entry(level + 1, i == 0);
+            self.member(val, level + 1, i + 1 < array.len())?;
+            self.ctx.path.pop();
+        }
+        if !self.flat {
+            self.out.push('\n');
+            self.indent(level);
+        }
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// Writes a value in a table or array, with the comma that follows it
+    /// and the comment after it. A collection written over several lines
+    /// has that comment after its opening bracket instead.
+    fn member(&mut self, val: &Value, level: usize, comma: bool) -> Result<(), Error> {
+        let after_here = match val {
+            Value::Table(t) if t.items.is_empty() => {
+                self.out.push_str("{}");
+                true
+            }
+            Value::Array(a) if a.is_empty() => {
+                self.out.push_str("[]");
+                true
+            }
+            Value::Table(_) | Value::Array(_) => !self.collection(val, level, comma)?,
+            val => {
+                self.scalar(val)?;
+                true
+            }
+        };
+        if comma && !self.flat {
+            self.out.push(',');
+        }
+        if after_here {
+            self.write_after();
+        }
+        Ok(())
+    }
+
+    /// Writes a collection that is not empty, on one line if it fits, and
+    /// returns whether it took more than one.
+    fn collection(&mut self, val: &Value, level: usize, comma: bool) -> Result<bool, Error> {
+        let write = |writer: &mut Self| match val {
+            Value::Table(t) => writer.table(t, level),
+            Value::Array(a) => writer.array(a, level),
+            _ => unreachable!("only collections"),
+        };
+        let try_flat = !self.flat
+            && (self.style.compact || self.style.line_width.is_some() && !self.comments_inside());
+        if !try_flat {
+            write(self)?;
+            return Ok(!self.flat);
+        }
+        let (start, column, reported) =
+            (self.out.len(), self.column(), self.ctx.report.entries.len());
+        self.flat = true;
+        let flat = write(self);
+        self.flat = false;
+        flat?;
+        let width = self.out[start..].chars().count() + usize::from(comma);
+        if self.style.fits(column, width) {
+            return Ok(false);
+        }
+        self.out.truncate(start);
+        self.ctx.report.entries.truncate(reported);
+        write(self)?;
+        Ok(true)
+    }
+
+    fn scalar(&mut self, val: &Value) -> Result<(), Error> {
+        match val {
+            Value::Null => self.out.push_str("null"),
+            Value::Bool(b) => self.out.push_str(&b.to_string()),
+            Value::Int(i) => self.out.push_str(&i.to_string()),
+            Value::UInt(i) => self.out.push_str(&i.to_string()),
+            Value::Float(f) => match serde_json::Number::from_f64(*f) {
+                Some(n) => self.out.push_str(&n.to_string()),
+                None if self.format == Format::Json5 => self.out.push_str(non_finite_name(*f)),
+                None => match self.ctx.options.non_finite {
+                    NonFinitePolicy::Error => {
+                        return Err(Error::unsupported(
+                            self.format,
+                            &self.ctx.path,
+                            Unsupported::NonFiniteFloat(*f),
+                        ))
+                    }
+                    NonFinitePolicy::Null => {
+                        self.ctx.lossy(LossKind::NonFiniteAsNull(*f));
+                        self.out.push_str("null");
+                    }
+                    NonFinitePolicy::String => {
+                        self.ctx.lossy(LossKind::NonFiniteAsString(*f));
+                        self.string(non_finite_name(*f));
+                    }
+                },
+            },
+            Value::String(s) => self.string(s),
+            Value::Datetime(dt) => {
+                self.ctx.lossy(LossKind::DatetimeAsString);
+                self.string(&dt.to_string());
+            }
+            Value::Array(_) | Value::Table(_) => unreachable!("collections are members"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_json5_literals() {
+        let text = r#"{a: 0x1F, b: -.5e1, c: 'it\'s\x21', d: -Infinity, e: [1,],}"#;
+        let (val, _) = parse(text, true).unwrap();
+        let Value::Table(t) = val else {
+            panic!("not a table")
+        };
+        assert_eq!(t.items["a"], Value::UInt(31));
+        assert_eq!(t.items["b"], Value::Float(-5.0));
+        assert_eq!(t.items["c"], Value::String("it's!".to_owned()));
+        assert_eq!(t.items["d"], Value::Float(f64::NEG_INFINITY));
+        assert_eq!(t.items["e"], Value::Array(vec![Value::UInt(1)]));
+    }
+
+    #[test]
+    fn reads_surrogate_escapes() {
+        let string = |text| match parse(text, false).unwrap().0 {
+            Value::String(s) => s,
+            val => panic!("{val:?} is not a string"),
+        };
+        assert_eq!(string(r#""\ud83d\ude00""#), "\u{1f600}");
+        assert_eq!(string(r#""\ud800\u0041""#), "\u{fffd}A");
+        assert_eq!(string(r#""\ud800\ud800x""#), "\u{fffd}\u{fffd}x");
+        assert_eq!(string(r#""\udc00""#), "\u{fffd}");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let text = format!("{{\"a\": {}{}}}", "[".repeat(100_000), "]".repeat(100_000));
+        let e = parse(&text, false).unwrap_err();
+        assert_eq!(e.message, "nesting deeper than 128 levels");
+        assert_eq!((e.line, e.column), (1, 134));
+        let text = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(parse(&text, false).is_ok());
+    }
+
+    #[test]
+    fn jsonc_rejects_json5_features() {
+        let e = parse("{\n  \"a\": 'x'\n}", false).unwrap_err();
+        assert_eq!(e.message, "single-quoted strings are JSON5, not JSONC");
+        assert_eq!((e.line, e.column), (2, 8));
+    }
+}
@@ -0,0 +1,70 @@
+//! Conversion between configuration formats: JSON, JSON Lines, JSONC, JSON5,
+//! TOML, YAML and INI.
+//!
+//! Every format is read into a [`Value`], and a document, whose top level is
+//! always a table, into a [`Table`]. Tables are written back out as any
+//! format with [`Table::to_string_with`], which also returns a
+//! [`ConversionReport`] of every value the target format could not hold
+//! exactly.
+//!
+//! ```
+//! use truns::{Format, StyleOptions, Table};
+//!
+//! let table = Table::parse("[server]\nport = 8080\n", Format::Toml)?;
+//! let yaml = table.to_string(Format::Yaml, &StyleOptions::default())?;
+//! assert_eq!(yaml, "---\nserver:\n  port: 8080\n");
+//! # Ok::<(), truns::Error>(())
+//! ```
+//!
+//! Each format family is behind a cargo feature of the same name: `json`
+//! (with JSON Lines, JSONC and JSON5), `toml`, `yaml` and `ini`. They are all
+//! on by default, along with `cli` for the `truns` binary. Other formats can
+//! be added at run time through the [`registry`].
+//!
+//! A [`Query`], in a small language modelled on jq, picks values out of a
+//! [`Value`] and transforms them, whatever format it was read from.
+
+pub mod comment;
+mod de;
+pub mod detect;
+pub mod error;
+pub mod format;
+#[cfg(feature = "json")]
+mod json5;
+pub mod options;
+pub mod parse;
+pub mod path;
+pub mod query;
+pub mod registry;
+pub mod report;
+pub mod roundtrip;
+mod ser;
+pub mod stream;
+pub mod style;
+pub mod table;
+#[cfg(feature = "toml")]
+mod toml_emitter;
+pub mod value;
+#[cfg(feature = "yaml")]
+mod yaml_emitter;
+#[cfg(feature = "yaml")]
+mod yaml_loader;
+
+pub use de::from_value;
+pub use error::Error;
+pub use format::Format;
+pub use options::Options;
+pub use path::Path;
+pub use query::Query;
+pub use report::ConversionReport;
+pub use ser::to_value;
+pub use stream::Stream;
+pub use style::StyleOptions;
+pub use table::Table;
+pub use value::{Datetime, Value};
+
+/// Converts a single document from one format to another, with default
+/// options and style.
+pub fn convert(text: &str, from: Format, to: Format) -> Result<String, Error> {
+    Table::parse(text, from)?.to_string(to, &StyleOptions::default())
+}
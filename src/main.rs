@@ -1,20 +1,689 @@
-use std::str::FromStr;
+use std::error::Error as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-mod value;
-mod table;
+use clap::{Args, Parser, Subcommand};
+use thiserror::Error;
 
-fn main() {
-    let input = r#"
-    {
-        "my_ass": true,
-        "my_men": {"ass": true, "no": 1}
+use truns::detect::{self, Confidence, Parsed};
+use truns::options::{
+    DatetimeStyle, LoadOptions, NonFinitePolicy, NullPolicy, Options, OverflowPolicy,
+};
+use truns::parse::{self, ParseError};
+use truns::path::Segment;
+use truns::report::ConversionReport;
+use truns::style::{KeyQuotes, StringQuotes, StyleOptions, YamlStyle};
+use truns::value::{IniOptions, IniPolicy, KeyPolicy};
+use truns::{registry, roundtrip};
+use truns::{Error, Format, Stream, Table, Value};
+
+#[derive(Parser)]
+#[command(version, about = "Convert between configuration formats")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a document from one format to another
+    Convert(ConvertArgs),
+    /// Convert a document to another format and back, and list every value
+    /// that changed on the way
+    CheckRoundtrip(RoundtripArgs),
+    /// Print the value at a path, like `servers[0].host`
+    Get(GetArgs),
+    /// Set the value at a path and write out the document
+    Set(SetArgs),
+    /// Run a jq-style query, like `.dependencies | keys`, on each document
+    /// and print every result
+    Query(QueryArgs),
+    /// List the formats with their file extensions and what they can hold
+    Formats,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Input file, or `-` for stdin
+    #[arg(default_value = "-")]
+    input: PathBuf,
+    /// Format of the input, detected from the extension or content if omitted
+    #[arg(short, long)]
+    from: Option<Format>,
+    /// Format of the output, see `truns formats`
+    #[arg(short, long)]
+    to: Format,
+    /// Output file, stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Sort keys alphabetically instead of keeping the input order
+    #[arg(long)]
+    sort_keys: bool,
+    /// Read and write JSON as an array with one document per element
+    #[arg(long)]
+    json_array: bool,
+    /// Write each document to its own file, numbered after the output file
+    #[arg(long, requires = "output")]
+    split: bool,
+    /// Fail instead of warning when the conversion changes or drops any value
+    #[arg(long)]
+    strict: bool,
+    /// Keep comments from TOML, YAML, JSONC and JSON5 input in output in those formats
+    #[arg(long)]
+    comments: bool,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    yaml: YamlArgs,
+    #[command(flatten)]
+    ini: IniArgs,
+}
+
+#[derive(Args)]
+struct RoundtripArgs {
+    /// Input file, or `-` for stdin
+    #[arg(default_value = "-")]
+    input: PathBuf,
+    /// Format of the input, detected from the extension or content if omitted
+    #[arg(short, long)]
+    from: Option<Format>,
+    /// Format to convert through
+    #[arg(long)]
+    via: Format,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    yaml: YamlArgs,
+    #[command(flatten)]
+    ini: IniArgs,
+}
+
+/// Paths into input with more than one document start with the index of the
+/// document, like `[1].name`.
+#[derive(Args)]
+struct GetArgs {
+    /// Input file, or `-` for stdin
+    input: PathBuf,
+    /// Path of the value, with keys that are not plain words quoted, like `a."b.c"[0]`
+    path: truns::Path,
+    /// Format of the input, detected from the extension or content if omitted
+    #[arg(short, long)]
+    from: Option<Format>,
+    /// Format to write tables and arrays in, the input format for tables and
+    /// JSON for arrays if omitted
+    #[arg(short, long)]
+    to: Option<Format>,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    yaml: YamlArgs,
+    #[command(flatten)]
+    ini: IniArgs,
+}
+
+#[derive(Args)]
+struct SetArgs {
+    /// Input file, or `-` for stdin
+    input: PathBuf,
+    /// Path of the value, made if missing along with the tables and arrays around it
+    path: truns::Path,
+    /// The new value, read as JSON if it is valid JSON and as a string otherwise
+    value: String,
+    /// Take the value as a string even if it is valid JSON
+    #[arg(long)]
+    string: bool,
+    /// Format of the input, detected from the extension or content if omitted
+    #[arg(short, long)]
+    from: Option<Format>,
+    /// Format of the output, the input format if omitted
+    #[arg(short, long)]
+    to: Option<Format>,
+    /// Output file, stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Write the output back to the input file
+    #[arg(short, long, conflicts_with = "output")]
+    in_place: bool,
+    /// Fail instead of warning when the conversion changes or drops any value
+    #[arg(long)]
+    strict: bool,
+    /// Keep comments from TOML, YAML, JSONC and JSON5 input
+    #[arg(long)]
+    comments: bool,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    yaml: YamlArgs,
+    #[command(flatten)]
+    ini: IniArgs,
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// The query, see the `truns::Query` docs for the language
+    query: truns::Query,
+    /// Input file, or `-` for stdin
+    #[arg(default_value = "-")]
+    input: PathBuf,
+    /// Format of the input, detected from the extension or content if omitted
+    #[arg(short, long)]
+    from: Option<Format>,
+    /// Format to write tables and arrays in, the input format for tables and
+    /// JSON for arrays if omitted
+    #[arg(short, long)]
+    to: Option<Format>,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    yaml: YamlArgs,
+    #[command(flatten)]
+    ini: IniArgs,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// How to write datetimes to YAML: native timestamps or strings
+    #[arg(long, default_value = "native")]
+    datetime: DatetimeStyle,
+    /// What to do with NaN and infinite floats in JSON and JSONC: error, null or string
+    #[arg(long, default_value = "error")]
+    non_finite: NonFinitePolicy,
+    /// What to do with integers above i64::MAX in TOML and YAML: error, string or float
+    #[arg(long, default_value = "error")]
+    uint_overflow: OverflowPolicy,
+    /// What to do with nulls in TOML: error, drop-keys, drop, replace or replace=TEXT
+    #[arg(long, default_value = "error")]
+    null: NullPolicy,
+}
+
+impl OutputArgs {
+    fn options(&self) -> Options {
+        Options {
+            datetime: self.datetime,
+            non_finite: self.non_finite,
+            uint_overflow: self.uint_overflow,
+            null: self.null.clone(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct StyleArgs {
+    /// Spaces per level of nesting, 4 for TOML arrays and 2 elsewhere if omitted
+    #[arg(long)]
+    indent: Option<usize>,
+    /// Write arrays and tables that fit in this many columns on one line
+    #[arg(long)]
+    line_width: Option<usize>,
+    /// Write everything on as few lines as possible, leaving out comments
+    #[arg(long)]
+    compact: bool,
+    /// Leave out the newline at the end of the output
+    #[arg(long)]
+    no_trailing_newline: bool,
+    /// Which keys to quote: minimal or always
+    #[arg(long, default_value = "minimal")]
+    key_quotes: KeyQuotes,
+    /// How to quote strings: minimal, double or single
+    #[arg(long, default_value = "minimal")]
+    string_quotes: StringQuotes,
+    /// How to write YAML collections: block or flow
+    #[arg(long, default_value = "block")]
+    yaml_style: YamlStyle,
+    /// Write TOML tables with at most this many values inline, 0 for never
+    #[arg(long, default_value_t = 0)]
+    toml_inline_tables: usize,
+    /// Write chains of TOML tables with one key each as dotted keys, like a.b.c = 1
+    #[arg(long)]
+    toml_dotted_keys: bool,
+}
+
+impl StyleArgs {
+    fn options(&self) -> StyleOptions {
+        StyleOptions {
+            indent: self.indent,
+            line_width: self.line_width,
+            compact: self.compact,
+            trailing_newline: !self.no_trailing_newline,
+            key_quotes: self.key_quotes,
+            string_quotes: self.string_quotes,
+            yaml: self.yaml_style,
+            toml_inline_tables: self.toml_inline_tables,
+            toml_dotted_keys: self.toml_dotted_keys,
+        }
+    }
+}
+
+#[derive(Args)]
+struct YamlArgs {
+    /// The most nodes YAML aliases may expand to, guarding against "billion laughs" input
+    #[arg(long, default_value_t = LoadOptions::default().alias_limit)]
+    yaml_alias_limit: usize,
+    /// What to do with YAML mapping keys that are not strings: stringify, error or keep
+    #[arg(long, default_value = "stringify")]
+    yaml_keys: KeyPolicy,
+}
+
+impl YamlArgs {
+    fn options(&self) -> LoadOptions {
+        LoadOptions {
+            alias_limit: self.yaml_alias_limit,
+        }
+    }
+}
+
+#[derive(Args)]
+struct IniArgs {
+    /// What to do with values INI cannot represent: error, skip or flatten
+    #[arg(long, default_value = "error")]
+    ini_policy: IniPolicy,
+    /// Read `true`, `false` and numbers in INI input as typed values
+    #[arg(long)]
+    ini_infer_types: bool,
+}
+
+impl IniArgs {
+    fn options(&self) -> IniOptions {
+        IniOptions {
+            policy: self.ini_policy,
+            infer_types: self.ini_infer_types,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+enum CliError {
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+    /// The source name, like the input path, and the error.
+    #[error("{1}")]
+    Parse(String, Box<ParseError>),
+    #[error("Could not detect the input format, pass it with --from")]
+    UnknownFormat,
+    #[error("Round trip through {0} changed {1} value{}", if *.1 == 1 { "" } else { "s" })]
+    RoundTrip(Format, usize),
+    #[error("The input has {1} documents but {0} holds one, pass --split or --json-array")]
+    MultipleDocuments(Format, usize),
+    #[error("The input has {0} documents, start the path with the index of one, like [0]")]
+    DocumentPath(usize),
+    #[error("Nothing at {0}")]
+    NotFound(truns::Path),
+    #[error("--in-place needs an input file")]
+    InPlaceStdin,
+    #[error(transparent)]
+    Convert(#[from] Error),
+}
+
+fn read_input(path: &Path) -> Result<String, CliError> {
+    if path.as_os_str() == "-" {
+        let mut buf = String::new();
+        io::stdin()
+            .read_to_string(&mut buf)
+            .map_err(|e| CliError::Io("<stdin>".to_owned(), e))?;
+        Ok(buf)
+    } else {
+        fs::read_to_string(path).map_err(|e| CliError::Io(path.display().to_string(), e))
+    }
+}
+
+fn write_output(path: Option<&Path>, content: &str) -> Result<(), CliError> {
+    match path {
+        Some(path) => {
+            fs::write(path, content).map_err(|e| CliError::Io(path.display().to_string(), e))
+        }
+        None => io::stdout()
+            .write_all(content.as_bytes())
+            .map_err(|e| CliError::Io("<stdout>".to_owned(), e)),
+    }
+}
+
+fn into_stream(
+    parsed: Parsed,
+    format: Format,
+    json_array: bool,
+    keys: KeyPolicy,
+    ini: &IniOptions,
+) -> Result<Stream, CliError> {
+    Ok(match parsed {
+        Parsed::Json(json) if json_array => Stream::from_json_array(json)?,
+        Parsed::Json(json) => Table::from_json(json)
+            .ok_or(Error::NotATable(Format::Json))?
+            .into(),
+        Parsed::JsonLines(lines) => Stream::from_json_lines(lines)?,
+        Parsed::Json5(value) | Parsed::Custom(value) => {
+            Table::from(value).ok_or(Error::NotATable(format))?.into()
+        }
+        Parsed::Toml(toml) => Table::from_toml(toml)
+            .ok_or(Error::NotATable(Format::Toml))?
+            .into(),
+        Parsed::Yaml(docs) if docs.is_empty() => Table::default().into(),
+        Parsed::Yaml(docs) => Stream::from_yaml_with(docs, keys)?,
+        Parsed::Ini(content) => Table::from_ini(&content, ini)?.into(),
+        _ => unreachable!("the `cli` feature enables every format"),
+    })
+}
+
+fn load(
+    text: &str,
+    path: &Path,
+    from: Option<Format>,
+    json_array: bool,
+    yaml: &YamlArgs,
+    ini: &IniOptions,
+) -> Result<(Stream, Format), CliError> {
+    let load_options = yaml.options();
+    let parse = |format| {
+        let name = match path.as_os_str() == "-" {
+            true => "<stdin>".to_owned(),
+            false => path.display().to_string(),
+        };
+        parse::parse(text, format, &load_options).map_err(|e| CliError::Parse(name, Box::new(e)))
+    };
+    let (parsed, format) = match from {
+        Some(format) => (parse(format)?, format),
+        None => {
+            let path = (path.as_os_str() != "-").then_some(path);
+            let detection = detect::detect(path, text).ok_or(CliError::UnknownFormat)?;
+            if detection.confidence == Confidence::Low {
+                eprintln!(
+                    "warning: guessed {} input with low confidence, pass --from to override",
+                    detection.format
+                );
+            }
+            match detection.parsed {
+                Some(parsed) => (parsed, detection.format),
+                None => (parse(detection.format)?, detection.format),
+            }
+        }
+    };
+    let stream = into_stream(parsed, format, json_array, yaml.yaml_keys, ini)?;
+    Ok((stream, format))
+}
+
+fn emit_stream(
+    stream: Stream,
+    format: Format,
+    json_array: bool,
+    options: &Options,
+    ini: &IniOptions,
+    style: &StyleOptions,
+) -> Result<(String, ConversionReport), CliError> {
+    Ok(match format {
+        Format::Yaml => stream.to_yaml_string(options, style)?,
+        Format::JsonLines => stream.to_json_lines(options, style)?,
+        Format::Json if json_array => stream.to_json_array_string(options, style)?,
+        _ => match <[Table; 1]>::try_from(stream.documents) {
+            Ok([table]) => table.to_string_with(format, options, ini, style)?,
+            Err(docs) => return Err(CliError::MultipleDocuments(format, docs.len())),
+        },
+    })
+}
+
+/// `out.json` becomes `out-1.json`, `out-2.json` and so on.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Prints the report as warnings, or fails with it in `--strict` mode.
+fn check(report: ConversionReport, format: Format, strict: bool) -> Result<(), CliError> {
+    if strict {
+        return Ok(report.strict(format)?);
+    }
+    for entry in &report.entries {
+        eprintln!("warning: {entry}");
+    }
+    Ok(())
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), CliError> {
+    let ini = args.ini.options();
+    let options = args.output_args.options();
+    let style = args.style.options();
+    let text = read_input(&args.input)?;
+    let (mut stream, from) = load(
+        &text,
+        &args.input,
+        args.from,
+        args.json_array,
+        &args.yaml,
+        &ini,
+    )?;
+    if args.comments {
+        stream.attach_comments(&text, from, &args.yaml.options());
+    }
+    if args.sort_keys {
+        stream.sort_keys();
+    }
+
+    match args.output.as_deref() {
+        Some(output) if args.split => {
+            for (i, table) in stream.documents.into_iter().enumerate() {
+                let (out, report) = table.to_string_with(args.to, &options, &ini, &style)?;
+                check(report, args.to, args.strict)?;
+                write_output(Some(&numbered(output, i + 1)), &out)?;
+            }
+            Ok(())
+        }
+        output => {
+            let (out, report) =
+                emit_stream(stream, args.to, args.json_array, &options, &ini, &style)?;
+            check(report, args.to, args.strict)?;
+            write_output(output, &out)
+        }
+    }
+}
+
+fn check_roundtrip(args: &RoundtripArgs) -> Result<(), CliError> {
+    let ini = args.ini.options();
+    let options = args.output_args.options();
+    let style = args.style.options();
+    let text = read_input(&args.input)?;
+    let (stream, from) = load(&text, &args.input, args.from, false, &args.yaml, &ini)?;
+
+    let nested = stream.documents.len() > 1;
+    let mut count = 0;
+    for (i, table) in stream.documents.iter().enumerate() {
+        let diffs =
+            roundtrip::check(table, from, args.via, &options, &ini, &style).map_err(|e| {
+                if nested {
+                    e.in_document(i)
+                } else {
+                    e
+                }
+            })?;
+        for mut diff in diffs {
+            if nested {
+                diff.path.prepend(Segment::Index(i));
+            }
+            println!("{diff}");
+            count += 1;
+        }
+    }
+    match count {
+        0 => Ok(()),
+        n => Err(CliError::RoundTrip(args.via, n)),
     }
-    "#;
+}
 
-    let json_v = serde_json::Value::from_str(input).unwrap();
-    let mut table = table::Table::from_json(json_v).unwrap();
+/// The document `path` goes into, and the rest of the path.
+fn document<'a>(
+    stream: &'a mut Stream,
+    path: &truns::Path,
+) -> Result<(&'a mut Table, truns::Path), CliError> {
+    let count = stream.documents.len();
+    if count == 1 {
+        return Ok((&mut stream.documents[0], path.clone()));
+    }
+    match path.segments().split_first() {
+        Some((Segment::Index(i), rest)) if *i < count => {
+            Ok((&mut stream.documents[*i], rest.iter().cloned().collect()))
+        }
+        _ => Err(CliError::DocumentPath(count)),
+    }
+}
 
-    println!("{:#?}", table.items);
+/// Writes a value picked out of a document: strings bare, other scalars as
+/// they display, tables as `to` or the input format, and arrays as `to` or
+/// JSON.
+fn show(
+    val: Value,
+    to: Option<Format>,
+    from: Format,
+    options: &Options,
+    ini: &IniOptions,
+    style: &StyleOptions,
+) -> Result<String, CliError> {
+    let to = match &val {
+        Value::String(s) => return Ok(format!("{s}\n")),
+        Value::Table(_) => to.unwrap_or(from),
+        Value::Array(_) => to.unwrap_or(Format::Json),
+        val => return Ok(format!("{val}\n")),
+    };
+    let (out, report) = val.to_string_with(to, options, ini, style)?;
+    check(report, to, false)?;
+    Ok(out)
+}
 
-    println!("\nTo JSON: {:?}", table.to_yaml());
+/// Prints strings bare, tables in `--to` or the input format, arrays in
+/// `--to` or JSON and other values as they are displayed.
+fn get(args: &GetArgs) -> Result<(), CliError> {
+    let ini = args.ini.options();
+    let text = read_input(&args.input)?;
+    let (mut stream, from) = load(&text, &args.input, args.from, false, &args.yaml, &ini)?;
+    let (table, path) = document(&mut stream, &args.path)?;
+    let val = table
+        .get_path(&path)
+        .cloned()
+        .ok_or_else(|| CliError::NotFound(args.path.clone()))?;
+    let options = args.output_args.options();
+    let out = show(val, args.to, from, &options, &ini, &args.style.options())?;
+    write_output(None, &out)
+}
+
+fn set(args: &SetArgs) -> Result<(), CliError> {
+    if args.in_place && args.input.as_os_str() == "-" {
+        return Err(CliError::InPlaceStdin);
+    }
+    let ini = args.ini.options();
+    let options = args.output_args.options();
+    let style = args.style.options();
+    let text = read_input(&args.input)?;
+    let (mut stream, from) = load(&text, &args.input, args.from, false, &args.yaml, &ini)?;
+    if args.comments {
+        stream.attach_comments(&text, from, &args.yaml.options());
+    }
+    let value = match serde_json::from_str(&args.value) {
+        Ok(value) if !args.string => value,
+        _ => Value::String(args.value.clone()),
+    };
+    let (table, path) = document(&mut stream, &args.path)?;
+    table.set_path(&path, value)?;
+
+    let to = args.to.unwrap_or(from);
+    let (out, report) = emit_stream(stream, to, false, &options, &ini, &style)?;
+    check(report, to, args.strict)?;
+    let output = match args.in_place {
+        true => Some(args.input.as_path()),
+        false => args.output.as_deref(),
+    };
+    write_output(output, &out)
+}
+
+fn query(args: &QueryArgs) -> Result<(), CliError> {
+    let ini = args.ini.options();
+    let options = args.output_args.options();
+    let style = args.style.options();
+    let text = read_input(&args.input)?;
+    let (stream, from) = load(&text, &args.input, args.from, false, &args.yaml, &ini)?;
+    for table in stream.documents {
+        for val in args.query.results(Value::Table(table)) {
+            write_output(None, &show(val?, args.to, from, &options, &ini, &style)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn list_formats() -> Result<(), CliError> {
+    for format in registry::formats() {
+        let extensions: Vec<String> = format
+            .extensions()
+            .iter()
+            .map(|e| format!(".{e}"))
+            .collect();
+        println!(
+            "{:<7}{:<17}{}",
+            format.name(),
+            extensions.join(" "),
+            format.capabilities().names().join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        Command::Convert(args) => convert(args),
+        Command::CheckRoundtrip(args) => check_roundtrip(args),
+        Command::Get(args) => get(args),
+        Command::Set(args) => set(args),
+        Command::Query(args) => query(args),
+        Command::Formats => list_formats(),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Parse(name, e)) => {
+            let Some(loc) = &e.location else {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            };
+            eprintln!("error: Failed to parse {}: {}", e.format, e.message);
+            eprintln!(
+                "{}--> {name}:{}:{}",
+                " ".repeat(loc.line.to_string().len()),
+                loc.line,
+                loc.column
+            );
+            eprintln!("{}", loc.snippet);
+            ExitCode::FAILURE
+        }
+        Err(CliError::Convert(e @ Error::Lossy { .. })) => {
+            eprintln!("error: {e}");
+            if let Error::Lossy { report, .. } = e {
+                for entry in &report.entries {
+                    eprintln!("  {entry}");
+                }
+            }
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            let mut message = e.to_string();
+            let mut source = e.source();
+            while let Some(e) = source {
+                message = format!("{message}: {e}");
+                source = e.source();
+            }
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
 }
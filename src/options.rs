@@ -0,0 +1,130 @@
+use std::str::FromStr;
+
+/// Settings for turning a [`Value`](crate::value::Value) into another format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub datetime: DatetimeStyle,
+    pub non_finite: NonFinitePolicy,
+    pub uint_overflow: OverflowPolicy,
+    pub null: NullPolicy,
+}
+
+/// Settings for reading YAML.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// The most nodes all alias expansions in a stream may add together.
+    pub alias_limit: usize,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            alias_limit: 100_000,
+        }
+    }
+}
+
+/// How datetimes are written to formats other than TOML, which always gets
+/// its own datetime type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DatetimeStyle {
+    /// Use the format's own datetime type: a plain YAML timestamp. JSON has
+    /// none, so it gets an RFC 3339 string.
+    #[default]
+    Native,
+    /// Always write an RFC 3339 string, quoted in YAML.
+    String,
+}
+
+impl FromStr for DatetimeStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Self::Native),
+            "string" => Ok(Self::String),
+            _ => Err(format!("unknown datetime style '{s}'")),
+        }
+    }
+}
+
+/// What to do with NaN and infinite floats in JSON, which has no way to write
+/// them. TOML and YAML have their own spellings and ignore this.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    #[default]
+    Error,
+    Null,
+    /// Write `"NaN"`, `"Infinity"` or `"-Infinity"`.
+    String,
+}
+
+impl FromStr for NonFinitePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "null" => Ok(Self::Null),
+            "string" => Ok(Self::String),
+            _ => Err(format!("unknown non-finite float policy '{s}'")),
+        }
+    }
+}
+
+/// What to do with unsigned integers above `i64::MAX`, which TOML and YAML
+/// integers can not hold. Anything but `Error` is noted in the
+/// [`ConversionReport`](crate::report::ConversionReport).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    /// Write the exact digits as a string.
+    String,
+    /// Write the nearest float, losing precision.
+    Float,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "string" => Ok(Self::String),
+            "float" => Ok(Self::Float),
+            _ => Err(format!("unknown integer overflow policy '{s}'")),
+        }
+    }
+}
+
+/// What to do with nulls in TOML, which has no null. Anything but `Error` is
+/// noted in the [`ConversionReport`](crate::report::ConversionReport).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum NullPolicy {
+    /// Fail, naming the path of the null.
+    #[default]
+    Error,
+    /// Leave out keys holding null. A null array element is still an error,
+    /// since leaving it out would shift the elements after it.
+    DropKeys,
+    /// Leave out keys holding null and null array elements.
+    Drop,
+    /// Write this string instead, like `""`.
+    Replace(String),
+}
+
+impl FromStr for NullPolicy {
+    type Err = String;
+    /// Takes `error`, `drop-keys`, `drop`, `replace` for an empty string or
+    /// `replace=TEXT`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "drop-keys" => Ok(Self::DropKeys),
+            "drop" => Ok(Self::Drop),
+            "replace" => Ok(Self::Replace(String::new())),
+            _ => match s.strip_prefix("replace=") {
+                Some(sentinel) => Ok(Self::Replace(sentinel.to_owned())),
+                None => Err(format!("unknown null policy '{s}'")),
+            },
+        }
+    }
+}
@@ -0,0 +1,235 @@
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use std::error::Error as StdError;
+use std::fmt;
+#[cfg(any(feature = "json", feature = "toml"))]
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::detect::Parsed;
+use crate::format::Format;
+#[cfg(feature = "json")]
+use crate::json5::{self, SyntaxError};
+use crate::options::LoadOptions;
+use crate::registry::{self, BoxError};
+#[cfg(feature = "yaml")]
+use crate::yaml_loader::{self, LoadError};
+
+/// A document that failed to parse, with the place it went wrong when the
+/// parser reports one.
+#[derive(Error, Debug)]
+pub struct ParseError {
+    pub format: Format,
+    /// The parser's message, without the position.
+    pub message: String,
+    pub location: Option<Location>,
+    /// The error the parser returned, when there is one.
+    #[source]
+    pub source: Option<BoxError>,
+}
+
+/// A position in the source text, both counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    /// The offending line with a caret under the column, see [`code_frame`].
+    pub snippet: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to parse {}: {}", self.format, self.message)?;
+        if let Some(loc) = &self.location {
+            write!(f, " at line {} column {}", loc.line, loc.column)?;
+        }
+        Ok(())
+    }
+}
+
+impl ParseError {
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+    fn new(
+        format: Format,
+        message: impl Into<String>,
+        text: &str,
+        line: usize,
+        column: usize,
+        source: impl StdError + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            format,
+            message: message.into(),
+            location: Some(Location::new(text, line, column)),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    fn without_location(
+        format: Format,
+        message: impl Into<String>,
+        source: Option<BoxError>,
+    ) -> Self {
+        Self {
+            format,
+            message: message.into(),
+            location: None,
+            source,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn json(text: &str, e: serde_json::Error) -> Self {
+        let column = char_column(text, e.line(), e.column());
+        Self::new(Format::Json, json_message(&e), text, e.line(), column, e)
+    }
+
+    #[cfg(feature = "json")]
+    fn json_line(text: &str, line: usize, e: serde_json::Error) -> Self {
+        let column = char_column(text, line, e.column());
+        Self::new(Format::JsonLines, json_message(&e), text, line, column, e)
+    }
+
+    #[cfg(feature = "toml")]
+    fn toml(text: &str, e: toml::de::Error) -> Self {
+        let message = e.message().trim_end().replace('\n', ", ");
+        match e.span() {
+            Some(span) => {
+                let before = &text[..span.start];
+                let line = before.matches('\n').count() + 1;
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                let column = before[line_start..].chars().count() + 1;
+                Self::new(Format::Toml, message, text, line, column, e)
+            }
+            None => Self::without_location(Format::Toml, message, Some(Box::new(e))),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    fn yaml(text: &str, e: LoadError) -> Self {
+        match e.position() {
+            Some((line, column)) => Self::new(Format::Yaml, e.to_string(), text, line, column, e),
+            None => Self::without_location(Format::Yaml, e.to_string(), Some(Box::new(e))),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn json5(text: &str, format: Format, e: SyntaxError) -> Self {
+        Self::new(format, e.message.clone(), text, e.line, e.column, e)
+    }
+
+    #[cfg(feature = "ini")]
+    fn ini(text: &str, e: ini::ParseError) -> Self {
+        Self::new(Format::Ini, e.msg.clone(), text, e.line, e.col, e)
+    }
+}
+
+/// `serde_json::Error` only exposes its message as part of its `Display`.
+#[cfg(feature = "json")]
+fn json_message(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+    match message.strip_suffix(&suffix) {
+        Some(message) => message.to_owned(),
+        None => message,
+    }
+}
+
+/// Turns the byte column `serde_json` reports on `line` into a column of
+/// characters, like the other parsers count. Past the end of the line, each
+/// byte counts as one.
+#[cfg(feature = "json")]
+fn char_column(text: &str, line: usize, column: usize) -> usize {
+    let source = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let byte = column.saturating_sub(1);
+    let before = source
+        .char_indices()
+        .filter(|(i, c)| i + c.len_utf8() <= byte)
+        .count();
+    before + byte.saturating_sub(source.len()) + 1
+}
+
+impl Location {
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+    fn new(text: &str, line: usize, column: usize) -> Self {
+        // Parsers that stop at the end of the input may point one past it.
+        let column = column.max(1);
+        Self {
+            line,
+            column,
+            snippet: code_frame(text, line, column),
+        }
+    }
+}
+
+/// Renders the given line of `text` in the style of rustc's diagnostics:
+///
+/// ```text
+///   |
+/// 3 | port = 80 80
+///   |           ^
+/// ```
+pub fn code_frame(text: &str, line: usize, column: usize) -> String {
+    let source = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs are kept so the caret lines up however they are displayed.
+    let pad: String = source
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{gutter} |\n{number} | {source}\n{gutter} | {pad}^")
+}
+
+/// Parses JSON Lines: one document per line, blank lines are skipped.
+#[cfg(feature = "json")]
+pub fn json_lines(text: &str) -> Result<Vec<serde_json::Value>, ParseError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::Value::from_str(line).map_err(|e| ParseError::json_line(text, i + 1, e))
+        })
+        .collect()
+}
+
+/// Parses `text` as `format`, keeping the source position of any error.
+#[cfg_attr(not(feature = "yaml"), allow(unused_variables))]
+pub fn parse(text: &str, format: Format, yaml: &LoadOptions) -> Result<Parsed, ParseError> {
+    Ok(match format {
+        #[cfg(feature = "json")]
+        Format::Json => {
+            Parsed::Json(serde_json::Value::from_str(text).map_err(|e| ParseError::json(text, e))?)
+        }
+        #[cfg(feature = "json")]
+        Format::JsonLines => Parsed::JsonLines(json_lines(text)?),
+        #[cfg(feature = "json")]
+        Format::Jsonc | Format::Json5 => {
+            let (value, _) = json5::parse(text, format == Format::Json5)
+                .map_err(|e| ParseError::json5(text, format, e))?;
+            Parsed::Json5(value)
+        }
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            Parsed::Toml(toml::Table::from_str(text).map_err(|e| ParseError::toml(text, e))?)
+        }
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            Parsed::Yaml(yaml_loader::load(text, yaml).map_err(|e| ParseError::yaml(text, e))?)
+        }
+        #[cfg(feature = "ini")]
+        Format::Ini => {
+            Parsed::Ini(ini::Ini::load_from_str(text).map_err(|e| ParseError::ini(text, e))?)
+        }
+        Format::Custom(name) => {
+            let custom = registry::get(name).ok_or_else(|| {
+                ParseError::without_location(format, "not a registered format", None)
+            })?;
+            let value = custom
+                .parse(text.as_bytes())
+                .map_err(|e| ParseError::without_location(format, e.to_string(), Some(e)))?;
+            Parsed::Custom(value)
+        }
+    })
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// The location of a value inside a document, written like `servers[3].tls.cert`.
+///
+/// Keys with characters other than letters, digits, `_` and `-` are written
+/// quoted in brackets, like `servers[3]["tls.cert"]`, and read back either
+/// that way or quoted after a dot, like `servers[3]."tls.cert"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+    /// Whether `prefix` is this path or one of the paths around it.
+    pub fn starts_with(&self, prefix: &Path) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+    pub fn prepend(&mut self, segment: Segment) {
+        self.segments.insert(0, segment);
+    }
+}
+
+/// Keys made of these characters are written bare, anything else is quoted.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.segments.is_empty() {
+            return f.write_str("<root>");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if is_bare_key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                Segment::Key(key) => write!(f, "[{key:?}]")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<Segment> for Path {
+    fn from_iter<I: IntoIterator<Item = Segment>>(iter: I) -> Self {
+        Self {
+            segments: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+    /// Reads a path as it is displayed. An empty string or `.` is the root.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = |message: &str| Error::InvalidPath {
+            path: s.to_owned(),
+            message: message.to_owned(),
+        };
+        let mut path = Path::new();
+        let mut rest = s.strip_prefix('.').unwrap_or(s);
+        while !rest.is_empty() {
+            if let Some(inner) = rest.strip_prefix('[') {
+                let (segment, after) = match inner.starts_with('"') {
+                    true => quoted(inner).map(|(key, after)| (Segment::Key(key), after)),
+                    false => {
+                        let end = inner.find(']').unwrap_or(inner.len());
+                        match inner[..end].trim().parse() {
+                            Ok(index) => Ok((Segment::Index(index), &inner[end..])),
+                            Err(_) => Err("expected an index or a quoted key in brackets"),
+                        }
+                    }
+                }
+                .map_err(invalid)?;
+                path.push(segment);
+                rest = after
+                    .strip_prefix(']')
+                    .ok_or_else(|| invalid("expected `]`"))?;
+                continue;
+            }
+            if !path.is_root() {
+                rest = rest
+                    .strip_prefix('.')
+                    .ok_or_else(|| invalid("expected `.` or `[`"))?;
+            }
+            if rest.starts_with('"') {
+                let (key, after) = quoted(rest).map_err(invalid)?;
+                path.push(Segment::Key(key));
+                rest = after;
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(invalid("empty key"));
+                }
+                path.push(Segment::Key(rest[..end].to_owned()));
+                rest = &rest[end..];
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Reads the key in double quotes at the start of `s`, with the escapes
+/// [`Path`] displays keys with, and returns it with the text after it.
+fn quoted(s: &str) -> Result<(String, &str), &'static str> {
+    let mut chars = s[1..].chars();
+    let mut key = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok((key, chars.as_str())),
+            '\\' => key.push(match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('"' | '\'' | '\\')) => c,
+                Some('u') if chars.next() == Some('{') => {
+                    let hex: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or("invalid unicode escape in quoted key")?
+                }
+                _ => return Err("unknown escape in quoted key"),
+            }),
+            c => key.push(c),
+        }
+    }
+    Err("unterminated quoted key")
+}
+
+/// A [`Path`], or text to parse into one, for the path methods of
+/// [`Table`](crate::Table).
+pub trait ToPath {
+    fn to_path(&self) -> Result<Cow<'_, Path>, Error>;
+}
+
+impl ToPath for Path {
+    fn to_path(&self) -> Result<Cow<'_, Path>, Error> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ToPath for str {
+    fn to_path(&self) -> Result<Cow<'_, Path>, Error> {
+        self.parse().map(Cow::Owned)
+    }
+}
+
+impl ToPath for String {
+    fn to_path(&self) -> Result<Cow<'_, Path>, Error> {
+        self.as_str().to_path()
+    }
+}
+
+impl<T: ToPath + ?Sized> ToPath for &T {
+    fn to_path(&self) -> Result<Cow<'_, Path>, Error> {
+        (**self).to_path()
+    }
+}
@@ -0,0 +1,2126 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::path::{Path, Segment};
+use crate::table::Table;
+use crate::value::Value;
+
+/// A query in a small language modelled on jq, which runs on any [`Value`]
+/// and so on documents of every format.
+///
+/// ```
+/// use truns::{Format, Query, Table, Value};
+///
+/// let toml = "[[servers]]\nhost = \"a\"\nport = 80\n\n[[servers]]\nhost = \"b\"\nport = 8080\n";
+/// let table = Table::parse(toml, Format::Toml)?;
+/// let query: Query = ".servers[] | select(.port > 1024) | .host".parse()?;
+/// assert_eq!(query.run(&Value::Table(table))?, [Value::String("b".to_owned())]);
+/// # Ok::<(), truns::Error>(())
+/// ```
+///
+/// A query takes one value and gives any number of results. It is built
+/// from:
+///
+/// - `.` for the input, `.name`, `."any key"`, `.[0]`, `.[-1]` and `.[1:3]`
+///   for what is in it, `.[]` for every element or value of it and `..` for
+///   every value in it at any depth. `?` after any of these ends its
+///   results quietly where it fails.
+/// - `a | b` to run `b` on every result of `a`, and `a, b` for the results of
+///   both.
+/// - literals, `[a]` to collect the results of `a` into an array, and
+///   `{key: a, "other key": b, (c): d, name}` for tables.
+/// - `+ - * / %`, `== != < <= > >=`, `and`, `or`, `a // b` for `b` when `a`
+///   has no results but `null` and `false`, and
+///   `if a then b elif c then d else e end`.
+/// - `.a = b`, `.a |= b` and `+= -= *= /= %= //=` to change the values at a
+///   path. `|=` runs `b` on the old value, the others on the input.
+/// - the functions `empty`, `error`, `not`, `length`, `type`, `keys`,
+///   `keys_unsorted`, `has(k)`, `contains(v)`, `add`, `any`, `all`, `range`,
+///   `flatten`, `map(f)`, `map_values(f)`, `select(f)`, the selectors
+///   `values`, `nulls`, `booleans`, `numbers`, `strings`, `arrays`,
+///   `objects`, `iterables` and `scalars`, `recurse`, `first`,
+///   `last`, `limit(n; f)`, `to_entries`, `from_entries`, `with_entries(f)`,
+///   `del(f)`, `path(f)`, `paths`, `sort`, `sort_by(f)`, `group_by(f)`,
+///   `unique`, `unique_by(f)`, `min`, `max`, `min_by(f)`, `max_by(f)`,
+///   `reverse`, `floor`, `ceil`, `round`, `abs`, `sqrt`, `pow(a; b)`, `log`,
+///   `tostring`, `tonumber`, `ascii_downcase`, `ascii_upcase`, `trim`,
+///   `ltrimstr(s)`, `rtrimstr(s)`, `startswith(s)`, `endswith(s)`,
+///   `split(s)` and `join(s)`, which work as they do in jq.
+///
+/// Unlike jq, there are no variables, `reduce`, `def`, `try` or regular
+/// expressions, integers stay integers where they can, and `type` calls
+/// datetimes `datetime`. Results are made one at a time as they are needed,
+/// so `first(range(1e12))` and `limit(n; f)` stop early, and a query stops at
+/// its first error.
+#[derive(Clone, Debug)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    /// Parses a query, which may nest no more than 128 levels deep, counting
+    /// every operator in a chain like `a | b | c` as one more level.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            tokens: lex(text)?,
+            pos: 0,
+            end: text.chars().count() + 1,
+            depth: 0,
+        };
+        let expr = parser.pipe()?;
+        match parser.peek() {
+            None => Ok(Self { expr }),
+            Some(_) => Err(parser.unexpected()),
+        }
+    }
+
+    /// Runs the query on `input` and returns its results.
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>, Error> {
+        self.results(input.clone()).collect()
+    }
+
+    /// Runs the query on `input`, making each result as it is asked for.
+    pub fn results(&self, input: Value) -> impl Iterator<Item = Result<Value, Error>> + '_ {
+        eval(&self.expr, Rc::new(input))
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::parse(s)
+    }
+}
+
+/***********************************************/
+// Parsing
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Dot,
+    DotDot,
+    /// `.name`
+    Field(String),
+    Ident(String),
+    Num(Value),
+    Str(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dot => f.write_str("`.`"),
+            Self::DotDot => f.write_str("`..`"),
+            Self::Field(name) => write!(f, "`.{name}`"),
+            Self::Ident(name) => write!(f, "`{name}`"),
+            Self::Num(n) => write!(f, "`{n}`"),
+            Self::Str(s) => write!(f, "{s:?}"),
+            Self::Punct(p) => write!(f, "`{p}`"),
+        }
+    }
+}
+
+/// Longest first, so that `|=` is not read as `|` and `=`.
+const PUNCTS: [&str; 31] = [
+    "//=", "|=", "+=", "-=", "*=", "/=", "%=", "==", "!=", "<=", ">=", "//", "|", ",", "[", "]",
+    "{", "}", "(", ")", ":", ";", "?", "+", "-", "*", "/", "%", "<", ">", "=",
+];
+
+fn invalid(column: usize, message: impl Into<String>) -> Error {
+    Error::InvalidQuery {
+        column,
+        message: message.into(),
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// Splits a query into tokens, with the column each starts at.
+fn lex(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    while let Some(&c) = chars.get(i) {
+        let column = i + 1;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '#' => {
+                while chars.get(i).is_some_and(|&c| c != '\n') {
+                    i += 1;
+                }
+                continue;
+            }
+            '.' if chars.get(i + 1) == Some(&'.') => {
+                i += 2;
+                Token::DotDot
+            }
+            '.' if chars.get(i + 1).is_some_and(|&c| is_ident_start(c)) => {
+                i += 1;
+                Token::Field(lex_ident(&chars, &mut i))
+            }
+            '.' => {
+                i += 1;
+                Token::Dot
+            }
+            c if is_ident_start(c) => Token::Ident(lex_ident(&chars, &mut i)),
+            c if c.is_ascii_digit() => Token::Num(
+                lex_number(&chars, &mut i).ok_or_else(|| invalid(column, "invalid number"))?,
+            ),
+            '"' => Token::Str(lex_string(&chars, &mut i).map_err(|m| invalid(column, m))?),
+            '$' => return Err(invalid(column, "variables are not supported")),
+            c => {
+                let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+                let punct = PUNCTS
+                    .iter()
+                    .find(|p| rest.starts_with(**p))
+                    .ok_or_else(|| invalid(column, format!("unexpected `{c}`")))?;
+                i += punct.len();
+                Token::Punct(punct)
+            }
+        };
+        out.push((token, column));
+    }
+    Ok(out)
+}
+
+fn lex_ident(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while chars
+        .get(*i)
+        .is_some_and(|&c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+fn lex_number(chars: &[char], i: &mut usize) -> Option<Value> {
+    let start = *i;
+    let digits = |i: &mut usize| {
+        while chars.get(*i).is_some_and(char::is_ascii_digit) {
+            *i += 1;
+        }
+    };
+    digits(i);
+    let mut float = false;
+    if chars.get(*i) == Some(&'.') && chars.get(*i + 1).is_some_and(char::is_ascii_digit) {
+        float = true;
+        *i += 1;
+        digits(i);
+    }
+    if matches!(chars.get(*i), Some('e' | 'E')) {
+        float = true;
+        *i += 1;
+        if matches!(chars.get(*i), Some('+' | '-')) {
+            *i += 1;
+        }
+        digits(i);
+    }
+    let text: String = chars[start..*i].iter().collect();
+    match text.parse() {
+        Ok(u) if !float => Some(Value::UInt(u)),
+        _ => text.parse().ok().map(Value::Float),
+    }
+}
+
+/// Reads a string in double quotes, with JSON escapes.
+fn lex_string(chars: &[char], i: &mut usize) -> Result<String, &'static str> {
+    let mut out = String::new();
+    *i += 1;
+    loop {
+        let c = *chars.get(*i).ok_or("unterminated string")?;
+        *i += 1;
+        match c {
+            '"' => return Ok(out),
+            '\\' => {
+                let escape = *chars.get(*i).ok_or("unterminated string")?;
+                *i += 1;
+                out.push(match escape {
+                    '"' | '\\' | '/' => escape,
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let mut code = lex_hex(chars, i)?;
+                        if (0xd800..0xdc00).contains(&code)
+                            && chars.get(*i) == Some(&'\\')
+                            && chars.get(*i + 1) == Some(&'u')
+                        {
+                            *i += 2;
+                            let low = lex_hex(chars, i)?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("invalid unicode escape");
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        char::from_u32(code).ok_or("invalid unicode escape")?
+                    }
+                    '(' => return Err("string interpolation is not supported"),
+                    _ => return Err("unknown escape in string"),
+                });
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+fn lex_hex(chars: &[char], i: &mut usize) -> Result<u32, &'static str> {
+    let hex: String = chars
+        .get(*i..*i + 4)
+        .ok_or("invalid unicode escape")?
+        .iter()
+        .collect();
+    *i += 4;
+    u32::from_str_radix(&hex, 16).map_err(|_| "invalid unicode escape")
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Identity,
+    /// `..`
+    Recurse,
+    Literal(Value),
+    /// `target[index]`, with the index run on the same input as the target.
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `target[]`
+    Iterate(Box<Expr>),
+    /// `target?`
+    Try(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// `a // b`
+    Alt(Box<Expr>, Box<Expr>),
+    /// `path |= f`
+    Update(Box<Expr>, Box<Expr>),
+    /// `path = value` and the arithmetic assignments.
+    Assign(AssignOp, Box<Expr>, Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AssignOp {
+    /// `=`
+    Set,
+    /// `+=` and the like.
+    Arith(Op),
+    /// `//=`
+    Alt,
+}
+
+/// The functions queries can call, by name and number of arguments.
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("empty", 0),
+    ("error", 0),
+    ("error", 1),
+    ("not", 0),
+    ("length", 0),
+    ("type", 0),
+    ("keys", 0),
+    ("keys_unsorted", 0),
+    ("has", 1),
+    ("contains", 1),
+    ("add", 0),
+    ("any", 0),
+    ("any", 1),
+    ("all", 0),
+    ("all", 1),
+    ("range", 1),
+    ("range", 2),
+    ("flatten", 0),
+    ("flatten", 1),
+    ("map", 1),
+    ("map_values", 1),
+    ("select", 1),
+    ("values", 0),
+    ("nulls", 0),
+    ("booleans", 0),
+    ("numbers", 0),
+    ("strings", 0),
+    ("arrays", 0),
+    ("objects", 0),
+    ("iterables", 0),
+    ("scalars", 0),
+    ("recurse", 0),
+    ("recurse", 1),
+    ("first", 0),
+    ("first", 1),
+    ("last", 0),
+    ("last", 1),
+    ("limit", 2),
+    ("to_entries", 0),
+    ("from_entries", 0),
+    ("with_entries", 1),
+    ("del", 1),
+    ("path", 1),
+    ("paths", 0),
+    ("sort", 0),
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique", 0),
+    ("unique_by", 1),
+    ("min", 0),
+    ("max", 0),
+    ("min_by", 1),
+    ("max_by", 1),
+    ("reverse", 0),
+    ("floor", 0),
+    ("ceil", 0),
+    ("round", 0),
+    ("abs", 0),
+    ("sqrt", 0),
+    ("pow", 2),
+    ("log", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("ascii_downcase", 0),
+    ("ascii_upcase", 0),
+    ("trim", 0),
+    ("ltrimstr", 1),
+    ("rtrimstr", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    ("split", 1),
+    ("join", 1),
+];
+
+/// A recursive descent parser, with one method per level of precedence from
+/// `|`, which binds loosest, down to single terms.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// The column just past the end of the query.
+    end: usize,
+    /// How deep the expression being parsed is nested.
+    depth: usize,
+}
+
+/// How deep a query may nest before it is refused, as running it could
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// The largest array index an assignment may create, as in jq.
+const MAX_INDEX: usize = 536_870_911;
+
+/// The longest string, in bytes, that repeating a string may make, as in jq.
+const MAX_REPEAT: usize = i32::MAX as usize;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |(_, column)| *column)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+        self.pos += usize::from(found);
+        found
+    }
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(word)) if word == keyword);
+        self.pos += usize::from(found);
+        found
+    }
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(token) => format!("found {token}"),
+            None => "found the end of the query".to_owned(),
+        }
+    }
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(invalid(
+                self.column(),
+                format!("expected `{punct}`, {}", self.found()),
+            )),
+        }
+    }
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        match self.eat_keyword(keyword) {
+            true => Ok(()),
+            false => Err(invalid(
+                self.column(),
+                format!("expected `{keyword}`, {}", self.found()),
+            )),
+        }
+    }
+    /// Goes one level deeper into the query, which must not go deeper than
+    /// [`MAX_DEPTH`]. Callers put `depth` back once they are done.
+    fn nest(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        match self.depth > MAX_DEPTH {
+            true => Err(invalid(
+                self.column(),
+                format!("the query nests more than {MAX_DEPTH} levels deep"),
+            )),
+            false => Ok(()),
+        }
+    }
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(token) => invalid(self.column(), format!("unexpected {token}")),
+            None => invalid(self.column(), "unexpected end of the query"),
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        self.nest()?;
+        let lhs = self.comma()?;
+        let expr = match self.eat("|") {
+            true => Expr::Pipe(Box::new(lhs), Box::new(self.pipe()?)),
+            false => lhs,
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+    fn comma(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut lhs = self.alt()?;
+        while self.eat(",") {
+            self.nest()?;
+            lhs = Expr::Comma(Box::new(lhs), Box::new(self.alt()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+    fn alt(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let lhs = self.assign()?;
+        let expr = match self.eat("//") {
+            true => {
+                self.nest()?;
+                Expr::Alt(Box::new(lhs), Box::new(self.alt()?))
+            }
+            false => lhs,
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+    fn assign(&mut self) -> Result<Expr, Error> {
+        let lhs = self.or()?;
+        let Some(Token::Punct(punct)) = self.peek() else {
+            return Ok(lhs);
+        };
+        let op = match *punct {
+            "|=" => None,
+            "=" => Some(AssignOp::Set),
+            "+=" => Some(AssignOp::Arith(Op::Add)),
+            "-=" => Some(AssignOp::Arith(Op::Sub)),
+            "*=" => Some(AssignOp::Arith(Op::Mul)),
+            "/=" => Some(AssignOp::Arith(Op::Div)),
+            "%=" => Some(AssignOp::Arith(Op::Rem)),
+            "//=" => Some(AssignOp::Alt),
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = Box::new(self.or()?);
+        Ok(match op {
+            None => Expr::Update(Box::new(lhs), rhs),
+            Some(op) => Expr::Assign(op, Box::new(lhs), rhs),
+        })
+    }
+    fn or(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut lhs = self.and()?;
+        while self.eat_keyword("or") {
+            self.nest()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+    fn and(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut lhs = self.compare()?;
+        while self.eat_keyword("and") {
+            self.nest()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.compare()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+    fn compare(&mut self) -> Result<Expr, Error> {
+        let lhs = self.additive()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => Op::Eq,
+            Some(Token::Punct("!=")) => Op::Ne,
+            Some(Token::Punct("<")) => Op::Lt,
+            Some(Token::Punct("<=")) => Op::Le,
+            Some(Token::Punct(">")) => Op::Gt,
+            Some(Token::Punct(">=")) => Op::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.additive()?)))
+    }
+    fn additive(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => Op::Add,
+                Some(Token::Punct("-")) => Op::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            self.nest()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.multiplicative()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+    fn multiplicative(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => Op::Mul,
+                Some(Token::Punct("/")) => Op::Div,
+                Some(Token::Punct("%")) => Op::Rem,
+                _ => break,
+            };
+            self.pos += 1;
+            self.nest()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+    fn unary(&mut self) -> Result<Expr, Error> {
+        match self.eat("-") {
+            true => Ok(Expr::Neg(Box::new(self.postfix()?))),
+            false => self.postfix(),
+        }
+    }
+    /// A term followed by `.name`, `[...]` and `?`.
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let mut expr = self.term()?;
+        loop {
+            if matches!(
+                self.peek(),
+                Some(Token::Field(_) | Token::Dot | Token::Punct("[" | "?"))
+            ) {
+                self.nest()?;
+            }
+            expr = match self.peek() {
+                Some(Token::Field(name)) => {
+                    let key = Expr::Literal(Value::String(name.clone()));
+                    self.pos += 1;
+                    Expr::Index(Box::new(expr), Box::new(key))
+                }
+                Some(Token::Dot) => match self.tokens.get(self.pos + 1) {
+                    Some((Token::Str(key), _)) => {
+                        let key = Expr::Literal(Value::String(key.clone()));
+                        self.pos += 2;
+                        Expr::Index(Box::new(expr), Box::new(key))
+                    }
+                    // `.a.[0]` is `.a[0]`.
+                    Some((Token::Punct("["), _)) => {
+                        self.pos += 1;
+                        continue;
+                    }
+                    _ => return Err(self.unexpected()),
+                },
+                Some(Token::Punct("[")) => {
+                    self.pos += 1;
+                    self.brackets(expr)?
+                }
+                Some(Token::Punct("?")) => {
+                    self.pos += 1;
+                    Expr::Try(Box::new(expr))
+                }
+                _ => break,
+            };
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+    /// The rest of `target[...]`, after the `[`.
+    fn brackets(&mut self, target: Expr) -> Result<Expr, Error> {
+        let target = Box::new(target);
+        if self.eat("]") {
+            return Ok(Expr::Iterate(target));
+        }
+        if self.eat(":") {
+            let to = self.pipe()?;
+            self.expect("]")?;
+            return Ok(Expr::Slice(target, None, Some(Box::new(to))));
+        }
+        let index = Box::new(self.pipe()?);
+        if self.eat(":") {
+            let to = match self.eat("]") {
+                true => None,
+                false => {
+                    let to = self.pipe()?;
+                    self.expect("]")?;
+                    Some(Box::new(to))
+                }
+            };
+            return Ok(Expr::Slice(target, Some(index), to));
+        }
+        self.expect("]")?;
+        Ok(Expr::Index(target, index))
+    }
+    fn term(&mut self) -> Result<Expr, Error> {
+        let column = self.column();
+        let Some(token) = self.next() else {
+            return Err(invalid(column, "unexpected end of the query"));
+        };
+        Ok(match token {
+            Token::Dot => match self.peek() {
+                Some(Token::Str(key)) => {
+                    let key = Expr::Literal(Value::String(key.clone()));
+                    self.pos += 1;
+                    Expr::Index(Box::new(Expr::Identity), Box::new(key))
+                }
+                _ => Expr::Identity,
+            },
+            Token::DotDot => Expr::Recurse,
+            Token::Field(name) => Expr::Index(
+                Box::new(Expr::Identity),
+                Box::new(Expr::Literal(Value::String(name))),
+            ),
+            Token::Num(n) => Expr::Literal(n),
+            Token::Str(s) => Expr::Literal(Value::String(s)),
+            Token::Punct("(") => {
+                let expr = self.pipe()?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Punct("[") => match self.eat("]") {
+                true => Expr::Array(None),
+                false => {
+                    let expr = self.pipe()?;
+                    self.expect("]")?;
+                    Expr::Array(Some(Box::new(expr)))
+                }
+            },
+            Token::Punct("{") => self.object()?,
+            Token::Ident(word) => match word.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                "if" => self.if_else()?,
+                "then" | "elif" | "else" | "end" | "and" | "or" => {
+                    return Err(invalid(column, format!("unexpected `{word}`")))
+                }
+                "reduce" | "foreach" | "def" | "as" | "try" | "catch" | "label" | "import"
+                | "include" => return Err(invalid(column, format!("`{word}` is not supported"))),
+                _ => self.call(word, column)?,
+            },
+            token => return Err(invalid(column, format!("unexpected {token}"))),
+        })
+    }
+    /// The rest of `if ... end`, after the `if` or `elif`.
+    fn if_else(&mut self) -> Result<Expr, Error> {
+        let cond = Box::new(self.pipe()?);
+        self.expect_keyword("then")?;
+        let then = Box::new(self.pipe()?);
+        if self.eat_keyword("elif") {
+            self.nest()?;
+            let otherwise = self.if_else()?;
+            self.depth -= 1;
+            return Ok(Expr::If(cond, then, Some(Box::new(otherwise))));
+        }
+        let otherwise = match self.eat_keyword("else") {
+            true => Some(Box::new(self.pipe()?)),
+            false => None,
+        };
+        self.expect_keyword("end")?;
+        Ok(Expr::If(cond, then, otherwise))
+    }
+    /// The rest of `{...}`, after the `{`.
+    fn object(&mut self) -> Result<Expr, Error> {
+        let mut entries = vec![];
+        if self.eat("}") {
+            return Ok(Expr::Object(entries));
+        }
+        loop {
+            let column = self.column();
+            let (key, name) = match self.next() {
+                Some(Token::Ident(name) | Token::Str(name)) => {
+                    (Expr::Literal(Value::String(name.clone())), Some(name))
+                }
+                Some(Token::Punct("(")) => {
+                    let key = self.pipe()?;
+                    self.expect(")")?;
+                    (key, None)
+                }
+                _ => return Err(invalid(column, "expected a key")),
+            };
+            let value = match (self.eat(":"), name) {
+                (true, _) => self.alt()?,
+                // `{name}` is `{name: .name}`.
+                (false, Some(name)) => Expr::Index(
+                    Box::new(Expr::Identity),
+                    Box::new(Expr::Literal(Value::String(name))),
+                ),
+                (false, None) => self.expect(":").map(|()| Expr::Identity)?,
+            };
+            entries.push((key, value));
+            if self.eat("}") {
+                return Ok(Expr::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+    /// A function call, after the name.
+    fn call(&mut self, name: String, column: usize) -> Result<Expr, Error> {
+        let mut args = vec![];
+        if self.eat("(") {
+            loop {
+                args.push(self.pipe()?);
+                if !self.eat(";") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        match FUNCTIONS.contains(&(name.as_str(), args.len())) {
+            true => Ok(Expr::Call(name, args)),
+            false => Err(invalid(
+                column,
+                format!("unknown function {name}/{}", args.len()),
+            )),
+        }
+    }
+}
+
+/***********************************************/
+// Running
+
+/// The results of a query, made as they are asked for, so that `first` and
+/// `limit` stop generators like `range` early.
+type Results<'a> = Box<dyn Iterator<Item = Result<Value, Error>> + 'a>;
+
+fn failed(message: impl Into<String>) -> Error {
+    Error::QueryFailed(message.into())
+}
+
+fn truthy(val: &Value) -> bool {
+    !matches!(val, Value::Null | Value::Bool(false))
+}
+
+fn from<'a>(result: Result<Value, Error>) -> Results<'a> {
+    Box::new(iter::once(result))
+}
+
+fn one<'a>(val: Value) -> Results<'a> {
+    from(Ok(val))
+}
+
+fn fail<'a>(err: Error) -> Results<'a> {
+    from(Err(err))
+}
+
+fn many<'a>(vals: Result<Vec<Value>, Error>) -> Results<'a> {
+    match vals {
+        Ok(vals) => Box::new(vals.into_iter().map(Ok)),
+        Err(err) => fail(err),
+    }
+}
+
+/// Results that `f` makes only once the first of them is asked for, for
+/// work that is not itself lazy.
+fn later<'a>(f: impl FnOnce() -> Results<'a> + 'a) -> Results<'a> {
+    Box::new(iter::once_with(f).flatten())
+}
+
+/// The results of `f` on each of `results`, which passes errors on.
+fn then<'a>(results: Results<'a>, mut f: impl FnMut(Value) -> Results<'a> + 'a) -> Results<'a> {
+    Box::new(results.flat_map(move |result| match result {
+        Ok(val) => f(val),
+        Err(err) => fail(err),
+    }))
+}
+
+/// `f` on each result of `outer` paired with each result of `inner`, which
+/// runs again for every result of `outer`.
+fn product<'a>(
+    outer: Results<'a>,
+    inner: impl Fn() -> Results<'a> + 'a,
+    f: impl Fn(&Value, &Value) -> Result<Value, Error> + Clone + 'a,
+) -> Results<'a> {
+    then(outer, move |outer| {
+        let f = f.clone();
+        Box::new(inner().map(move |inner| f(&outer, &inner?)))
+    })
+}
+
+/// All results of `expr`, for the places that need them at once.
+fn collect(expr: &Expr, input: &Value) -> Result<Vec<Value>, Error> {
+    eval(expr, Rc::new(input.clone())).collect()
+}
+
+fn eval(expr: &Expr, input: Rc<Value>) -> Results<'_> {
+    match expr {
+        Expr::Identity => one(Rc::unwrap_or_clone(input)),
+        Expr::Recurse => descend(input),
+        Expr::Literal(val) => one(val.clone()),
+        Expr::Index(target, index) => product(
+            eval(target, input.clone()),
+            move || eval(index, input.clone()),
+            index_value,
+        ),
+        Expr::Slice(target, from, to) => {
+            fn bound(bound: &Option<Box<Expr>>, input: Rc<Value>) -> Results<'_> {
+                match bound {
+                    Some(expr) => eval(expr, input),
+                    None => one(Value::Null),
+                }
+            }
+            then(eval(target, input.clone()), move |target| {
+                let (target, input) = (Rc::new(target), input.clone());
+                then(bound(from, input.clone()), move |from| {
+                    let target = target.clone();
+                    Box::new(bound(to, input.clone()).map(move |to| slice(&target, &from, &to?)))
+                })
+            })
+        }
+        Expr::Iterate(target) => then(eval(target, input), |target| many(iterate(target))),
+        Expr::Try(expr) => Box::new(eval(expr, input).map_while(Result::ok).map(Ok)),
+        Expr::Pipe(lhs, rhs) => then(eval(lhs, input), move |val| eval(rhs, Rc::new(val))),
+        Expr::Comma(lhs, rhs) => {
+            Box::new(eval(lhs, input.clone()).chain(later(move || eval(rhs, input))))
+        }
+        Expr::Neg(expr) => Box::new(eval(expr, input).map(|val| negate(&val?))),
+        Expr::Binary(op, lhs, rhs) => {
+            let op = *op;
+            product(
+                eval(rhs, input.clone()),
+                move || eval(lhs, input.clone()),
+                move |rhs, lhs| binary(op, lhs, rhs),
+            )
+        }
+        Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+            let or = matches!(expr, Expr::Or(..));
+            then(eval(lhs, input.clone()), move |lhs| {
+                match truthy(&lhs) == or {
+                    true => one(Value::Bool(or)),
+                    false => {
+                        Box::new(eval(rhs, input.clone()).map(|rhs| Ok(Value::Bool(truthy(&rhs?)))))
+                    }
+                }
+            })
+        }
+        Expr::Alt(lhs, rhs) => later(move || {
+            let mut found = eval(lhs, input.clone())
+                .filter_map(Result::ok)
+                .filter(truthy)
+                .peekable();
+            match found.peek() {
+                Some(_) => Box::new(found.map(Ok)),
+                None => eval(rhs, input),
+            }
+        }),
+        Expr::Update(lhs, f) => later(move || from(update(lhs, f, &input))),
+        Expr::Assign(op, lhs, rhs) => later(move || {
+            let paths = match paths(lhs, &Path::new(), &input) {
+                Ok(paths) => paths,
+                Err(err) => return fail(err),
+            };
+            let op = *op;
+            Box::new(eval(rhs, input.clone()).map(move |val| assign(op, &paths, &input, val?)))
+        }),
+        Expr::Array(None) => one(Value::Array(vec![])),
+        Expr::Array(Some(expr)) => later(move || {
+            from(
+                eval(expr, input)
+                    .collect::<Result<_, _>>()
+                    .map(Value::Array),
+            )
+        }),
+        Expr::Object(entries) => later(move || many(object(entries, &input))),
+        Expr::If(cond, when, otherwise) => then(eval(cond, input.clone()), move |cond| {
+            match (truthy(&cond), otherwise) {
+                (true, _) => eval(when, input.clone()),
+                (false, Some(otherwise)) => eval(otherwise, input.clone()),
+                (false, None) => one(Value::clone(&input)),
+            }
+        }),
+        Expr::Call(name, args) => call(name, args, input),
+    }
+}
+
+/// The tables `{key: value, ...}` makes, one for each combination of the
+/// results of its keys and values.
+fn object(entries: &[(Expr, Expr)], input: &Value) -> Result<Vec<Value>, Error> {
+    let mut tables = vec![Table::default()];
+    for (key, val) in entries {
+        let vals = collect(val, input)?;
+        let mut next = vec![];
+        for key in collect(key, input)? {
+            let Value::String(key) = key else {
+                return Err(failed(format!(
+                    "Table keys must be strings, not {}",
+                    key.kind()
+                )));
+            };
+            for table in &tables {
+                for val in &vals {
+                    let mut table = table.clone();
+                    table.items.insert(key.clone(), val.clone());
+                    next.push(table);
+                }
+            }
+        }
+        tables = next;
+    }
+    Ok(tables.into_iter().map(Value::Table).collect())
+}
+
+/// `val` and every value in it, depth first, with their paths below `path`.
+fn descendants(path: &Path, val: &Value, out: &mut Vec<(Path, Value)>) {
+    out.push((path.clone(), val.clone()));
+    match val {
+        Value::Array(a) => {
+            for (i, val) in a.iter().enumerate() {
+                descendants(&child(path, Segment::Index(i)), val, out);
+            }
+        }
+        Value::Table(t) => {
+            for (key, val) in &t.items {
+                descendants(&child(path, Segment::Key(key.clone())), val, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `..`: `input` and every value in it, depth first. Each value is found
+/// from the top by its position and cloned only once it is reached.
+fn descend(input: Rc<Value>) -> Results<'static> {
+    let mut stack = vec![vec![]];
+    Box::new(iter::from_fn(move || {
+        let at: Vec<usize> = stack.pop()?;
+        let val = at.iter().fold(&*input, |val, &i| match val {
+            Value::Array(a) => &a[i],
+            Value::Table(t) => &t.items[i],
+            _ => unreachable!("only arrays and tables have values in them"),
+        });
+        let len = match val {
+            Value::Array(a) => a.len(),
+            Value::Table(t) => t.items.len(),
+            _ => 0,
+        };
+        stack.extend((0..len).rev().map(|i| [&at[..], &[i]].concat()));
+        Some(Ok(val.clone()))
+    }))
+}
+
+fn child(path: &Path, segment: Segment) -> Path {
+    let mut path = path.clone();
+    path.push(segment);
+    path
+}
+
+/// Keys and indexes in messages: strings quoted and numbers as they are.
+fn describe(index: &Value) -> Cow<'static, str> {
+    match index {
+        Value::String(_) | Value::Int(_) | Value::UInt(_) | Value::Float(_) => {
+            Cow::Owned(index.to_string())
+        }
+        index => Cow::Borrowed(index.kind()),
+    }
+}
+
+fn index_value(target: &Value, index: &Value) -> Result<Value, Error> {
+    match (target, index) {
+        (Value::Table(t), Value::String(key)) => Ok(t.items.get(key).cloned().unwrap_or_default()),
+        (Value::Array(a), index) if num(index).is_some() => Ok(position(a.len(), index)
+            .and_then(|i| a.get(i))
+            .cloned()
+            .unwrap_or_default()),
+        (Value::Null, Value::String(_)) => Ok(Value::Null),
+        (Value::Null, index) if num(index).is_some() => Ok(Value::Null),
+        (target, index) => Err(cannot_index(target, index)),
+    }
+}
+
+fn cannot_index(target: &Value, index: &Value) -> Error {
+    failed(format!(
+        "Can not index {} with {}",
+        target.kind(),
+        describe(index)
+    ))
+}
+
+/// The position in an array of `len` elements that the number `index`
+/// points at, counting from the end if it is negative.
+fn position(len: usize, index: &Value) -> Option<usize> {
+    let i = num(index)?.floor();
+    usize::try_from(if i < 0 { len as i128 + i } else { i }).ok()
+}
+
+fn slice(target: &Value, from: &Value, to: &Value) -> Result<Value, Error> {
+    let len = match target {
+        Value::Array(a) => a.len(),
+        Value::String(s) => s.chars().count(),
+        Value::Null => return Ok(Value::Null),
+        target => return Err(failed(format!("Can not slice {}", target.kind()))),
+    };
+    let bound = |bound: &Value, default: usize| match (bound, num(bound)) {
+        (Value::Null, _) => Ok(default),
+        (_, Some(n)) => {
+            let i = n.floor();
+            let i = if i < 0 { len as i128 + i } else { i };
+            Ok(i.clamp(0, len as i128) as usize)
+        }
+        (bound, None) => Err(failed(format!("Can not slice with {}", describe(bound)))),
+    };
+    let start = bound(from, 0)?;
+    let end = bound(to, len)?.max(start);
+    Ok(match target {
+        Value::Array(a) => Value::Array(a[start..end].to_vec()),
+        Value::String(s) => Value::String(s.chars().skip(start).take(end - start).collect()),
+        _ => unreachable!("only arrays and strings have a length to slice"),
+    })
+}
+
+fn iterate(val: Value) -> Result<Vec<Value>, Error> {
+    match val {
+        Value::Array(a) => Ok(a),
+        Value::Table(t) => Ok(t.items.into_values().collect()),
+        val => Err(cannot_iterate(&val)),
+    }
+}
+
+fn cannot_iterate(val: &Value) -> Error {
+    failed(format!("Can not iterate over {}", val.kind()))
+}
+
+/***********************************************/
+// Paths
+
+/// The values `expr` picks out of `input`, with their paths below `path`,
+/// for assignments, `del` and `path`. Only queries that pick values out of
+/// their input have paths.
+fn paths(expr: &Expr, path: &Path, input: &Value) -> Result<Vec<(Path, Value)>, Error> {
+    let mut out = vec![];
+    match expr {
+        Expr::Identity => out.push((path.clone(), input.clone())),
+        Expr::Recurse | Expr::Call(_, _) if is_recurse(expr) => descendants(path, input, &mut out),
+        Expr::Index(target, index) => {
+            let indexes = collect(index, input)?;
+            for (path, target) in paths(target, path, input)? {
+                for index in &indexes {
+                    let segment = match (&target, index) {
+                        (Value::Table(_) | Value::Null, Value::String(key)) => {
+                            Segment::Key(key.clone())
+                        }
+                        (Value::Array(_) | Value::Null, index) if num(index).is_some() => {
+                            let len = match &target {
+                                Value::Array(a) => a.len(),
+                                _ => 0,
+                            };
+                            Segment::Index(position(len, index).ok_or_else(|| {
+                                failed(format!("Index {} is out of bounds", describe(index)))
+                            })?)
+                        }
+                        (target, index) => return Err(cannot_index(target, index)),
+                    };
+                    let val = index_value(&target, index)?;
+                    out.push((child(&path, segment), val));
+                }
+            }
+        }
+        Expr::Iterate(target) => {
+            for (path, target) in paths(target, path, input)? {
+                match target {
+                    Value::Array(a) => out.extend(
+                        a.into_iter()
+                            .enumerate()
+                            .map(|(i, val)| (child(&path, Segment::Index(i)), val)),
+                    ),
+                    Value::Table(t) => out.extend(
+                        t.items
+                            .into_iter()
+                            .map(|(key, val)| (child(&path, Segment::Key(key)), val)),
+                    ),
+                    target => return Err(cannot_iterate(&target)),
+                }
+            }
+        }
+        Expr::Try(expr) => out = paths(expr, path, input).unwrap_or_default(),
+        Expr::Pipe(lhs, rhs) => {
+            for (path, val) in paths(lhs, path, input)? {
+                out.extend(paths(rhs, &path, &val)?);
+            }
+        }
+        Expr::Comma(lhs, rhs) => {
+            out = paths(lhs, path, input)?;
+            out.extend(paths(rhs, path, input)?);
+        }
+        Expr::Alt(lhs, rhs) => {
+            out = paths(lhs, path, input).unwrap_or_default();
+            out.retain(|(_, val)| truthy(val));
+            if out.is_empty() {
+                out = paths(rhs, path, input)?;
+            }
+        }
+        Expr::If(cond, then, otherwise) => {
+            for cond in collect(cond, input)? {
+                match (truthy(&cond), otherwise) {
+                    (true, _) => out.extend(paths(then, path, input)?),
+                    (false, Some(otherwise)) => out.extend(paths(otherwise, path, input)?),
+                    (false, None) => out.push((path.clone(), input.clone())),
+                }
+            }
+        }
+        Expr::Call(name, args) => match (name.as_str(), args.as_slice()) {
+            ("empty", []) => {}
+            ("select", [f]) => {
+                for val in collect(f, input)? {
+                    if truthy(&val) {
+                        out.push((path.clone(), input.clone()));
+                    }
+                }
+            }
+            (name, []) if selects(name, input).is_some() => {
+                if selects(name, input) == Some(true) {
+                    out.push((path.clone(), input.clone()));
+                }
+            }
+            ("first", [f]) => out.extend(paths(f, path, input)?.into_iter().take(1)),
+            ("last", [f]) => out.extend(paths(f, path, input)?.pop()),
+            _ => return Err(not_a_path()),
+        },
+        _ => return Err(not_a_path()),
+    }
+    Ok(out)
+}
+
+fn is_recurse(expr: &Expr) -> bool {
+    match expr {
+        Expr::Recurse => true,
+        Expr::Call(name, args) => name == "recurse" && args.is_empty(),
+        _ => false,
+    }
+}
+
+fn not_a_path() -> Error {
+    failed("Only paths like .a, .[0], .[] and .. can be assigned to or deleted")
+}
+
+/// The value at `path` in `val`, made along with the tables and arrays around
+/// it if missing.
+fn slot<'a>(mut val: &'a mut Value, path: &Path) -> Result<&'a mut Value, Error> {
+    for segment in path.segments() {
+        if let Value::Null = val {
+            *val = match segment {
+                Segment::Key(_) => Value::Table(Table::default()),
+                Segment::Index(_) => Value::Array(vec![]),
+            };
+        }
+        val = match (val, segment) {
+            (Value::Table(t), Segment::Key(key)) => t.items.entry(key.clone()).or_default(),
+            (Value::Array(a), Segment::Index(i)) => {
+                if a.len() <= *i {
+                    if *i > MAX_INDEX || a.try_reserve(i + 1 - a.len()).is_err() {
+                        return Err(failed("Array index too large"));
+                    }
+                    a.resize(i + 1, Value::Null);
+                }
+                &mut a[*i]
+            }
+            (val, Segment::Key(key)) => {
+                return Err(failed(format!("Can not index {} with {key:?}", val.kind())))
+            }
+            (val, Segment::Index(i)) => {
+                return Err(failed(format!("Can not index {} with {i}", val.kind())))
+            }
+        };
+    }
+    Ok(val)
+}
+
+/// Runs `f` on the value at each path of `lhs` and puts its first result in
+/// its place, or deletes the value if `f` has none.
+fn update(lhs: &Expr, f: &Expr, input: &Value) -> Result<Value, Error> {
+    let mut out = input.clone();
+    let mut deleted = vec![];
+    for (path, _) in paths(lhs, &Path::new(), input)? {
+        let slot = slot(&mut out, &path)?;
+        match eval(f, Rc::new(slot.clone())).next().transpose()? {
+            Some(val) => *slot = val,
+            None => deleted.push(path),
+        }
+    }
+    delete(&mut out, deleted);
+    Ok(out)
+}
+
+/// Puts `val` at each of `paths` in a copy of `input`, or combines it with
+/// the value already there.
+fn assign(
+    op: AssignOp,
+    paths: &[(Path, Value)],
+    input: &Value,
+    val: Value,
+) -> Result<Value, Error> {
+    let mut out = input.clone();
+    for (path, _) in paths {
+        let slot = slot(&mut out, path)?;
+        *slot = match op {
+            AssignOp::Set => val.clone(),
+            AssignOp::Arith(op) => binary(op, slot, &val)?,
+            AssignOp::Alt if truthy(slot) => continue,
+            AssignOp::Alt => val.clone(),
+        };
+    }
+    Ok(out)
+}
+
+/// Deletes the values at `paths`, from the last to the first so that
+/// deleting array elements does not move the ones still to delete.
+fn delete(val: &mut Value, mut paths: Vec<Path>) {
+    paths.sort_by(|a, b| compare_paths(b, a));
+    paths.dedup();
+    for path in paths {
+        let Some((last, parent)) = path.segments().split_last() else {
+            *val = Value::Null;
+            continue;
+        };
+        let Some(target) = value_at(val, parent) else {
+            continue;
+        };
+        match (target, last) {
+            (Value::Table(t), Segment::Key(key)) => {
+                t.items.shift_remove(key);
+            }
+            (Value::Array(a), Segment::Index(i)) if *i < a.len() => {
+                a.remove(*i);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn value_at<'a>(mut val: &'a mut Value, path: &[Segment]) -> Option<&'a mut Value> {
+    for segment in path {
+        val = match (val, segment) {
+            (Value::Table(t), Segment::Key(key)) => t.items.get_mut(key)?,
+            (Value::Array(a), Segment::Index(i)) => a.get_mut(*i)?,
+            _ => return None,
+        };
+    }
+    Some(val)
+}
+
+fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    for (a, b) in a.segments().iter().zip(b.segments()) {
+        let order = match (a, b) {
+            (Segment::Key(a), Segment::Key(b)) => a.cmp(b),
+            (Segment::Index(a), Segment::Index(b)) => a.cmp(b),
+            (Segment::Key(_), Segment::Index(_)) => Ordering::Greater,
+            (Segment::Index(_), Segment::Key(_)) => Ordering::Less,
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.segments().len().cmp(&b.segments().len())
+}
+
+/// A path as jq writes it, an array of keys and indexes.
+fn path_value(path: &Path) -> Value {
+    Value::Array(
+        path.segments()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => Value::String(key.clone()),
+                Segment::Index(i) => Value::UInt(*i as u64),
+            })
+            .collect(),
+    )
+}
+
+/***********************************************/
+// Arithmetic and comparison
+
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+impl Num {
+    fn float(self) -> f64 {
+        match self {
+            Self::Int(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+    fn floor(self) -> i128 {
+        match self {
+            Self::Int(i) => i,
+            Self::Float(f) => f.floor() as i128,
+        }
+    }
+}
+
+fn num(val: &Value) -> Option<Num> {
+    match val {
+        Value::Int(i) => Some(Num::Int((*i).into())),
+        Value::UInt(i) => Some(Num::Int((*i).into())),
+        Value::Float(f) => Some(Num::Float(*f)),
+        _ => None,
+    }
+}
+
+/// Like the parsers, keeps integers that are not negative as `UInt`, and
+/// falls back to a float past the 64-bit range.
+fn int_value(i: i128) -> Value {
+    match (u64::try_from(i), i64::try_from(i)) {
+        (Ok(u), _) => Value::UInt(u),
+        (_, Ok(i)) => Value::Int(i),
+        _ => Value::Float(i as f64),
+    }
+}
+
+/// An integer for whole floats, so that `round` and the like give integers.
+fn whole(f: f64) -> Value {
+    match f.is_finite() && f.abs() < 1e38 {
+        true => int_value(f as i128),
+        false => Value::Float(f),
+    }
+}
+
+fn arith(
+    lhs: &Value,
+    rhs: &Value,
+    int: fn(i128, i128) -> Option<i128>,
+    float: fn(f64, f64) -> f64,
+) -> Option<Value> {
+    Some(match (num(lhs)?, num(rhs)?) {
+        (Num::Int(a), Num::Int(b)) => match int(a, b) {
+            Some(i) => int_value(i),
+            None => Value::Float(float(a as f64, b as f64)),
+        },
+        (a, b) => Value::Float(float(a.float(), b.float())),
+    })
+}
+
+fn negate(val: &Value) -> Result<Value, Error> {
+    match num(val) {
+        Some(Num::Int(i)) => Ok(int_value(-i)),
+        Some(Num::Float(f)) => Ok(Value::Float(-f)),
+        None => Err(failed(format!("Can not negate {}", val.kind()))),
+    }
+}
+
+fn binary(op: Op, lhs: &Value, rhs: &Value) -> Result<Value, Error> {
+    let order = compare(lhs, rhs);
+    let cannot = |what: &str, join: &str| {
+        failed(format!(
+            "Can not {what} {} {join} {}",
+            lhs.kind(),
+            rhs.kind()
+        ))
+    };
+    Ok(match op {
+        Op::Eq => Value::Bool(order == Ordering::Equal),
+        Op::Ne => Value::Bool(order != Ordering::Equal),
+        Op::Lt => Value::Bool(order == Ordering::Less),
+        Op::Le => Value::Bool(order != Ordering::Greater),
+        Op::Gt => Value::Bool(order == Ordering::Greater),
+        Op::Ge => Value::Bool(order != Ordering::Less),
+        Op::Add => match (lhs, rhs) {
+            (Value::Null, val) | (val, Value::Null) => val.clone(),
+            (Value::String(a), Value::String(b)) => Value::String(format!("{a}{b}")),
+            (Value::Array(a), Value::Array(b)) => Value::Array([a.as_slice(), b].concat()),
+            (Value::Table(a), Value::Table(b)) => {
+                let mut table = a.clone();
+                for (key, val) in &b.items {
+                    table.items.insert(key.clone(), val.clone());
+                }
+                Value::Table(table)
+            }
+            _ => arith(lhs, rhs, i128::checked_add, |a, b| a + b)
+                .ok_or_else(|| cannot("add", "and"))?,
+        },
+        Op::Sub => match (lhs, rhs) {
+            (Value::Array(a), Value::Array(b)) => Value::Array(
+                a.iter()
+                    .filter(|a| !b.iter().any(|b| compare(a, b) == Ordering::Equal))
+                    .cloned()
+                    .collect(),
+            ),
+            _ => arith(lhs, rhs, i128::checked_sub, |a, b| a - b).ok_or_else(|| {
+                failed(format!(
+                    "Can not subtract {} from {}",
+                    rhs.kind(),
+                    lhs.kind()
+                ))
+            })?,
+        },
+        Op::Mul => match (lhs, rhs) {
+            (Value::String(s), n) | (n, Value::String(s)) if num(n).is_some() => {
+                match num(n).map_or(0, Num::floor) {
+                    n if n <= 0 => Value::Null,
+                    n => {
+                        let n = usize::try_from(n).unwrap_or(usize::MAX);
+                        match s.len().checked_mul(n) {
+                            Some(len) if len <= MAX_REPEAT => Value::String(s.repeat(n)),
+                            _ => return Err(failed("Repeat string result too long")),
+                        }
+                    }
+                }
+            }
+            (Value::Table(a), Value::Table(b)) => Value::Table(merge(a, b)),
+            _ => arith(lhs, rhs, i128::checked_mul, |a, b| a * b)
+                .ok_or_else(|| cannot("multiply", "and"))?,
+        },
+        Op::Div => match (lhs, rhs) {
+            (Value::String(s), Value::String(sep)) => split(s, sep),
+            _ if num(rhs).is_some_and(|n| n.float() == 0.0) && num(lhs).is_some() => {
+                return Err(failed(format!("Can not divide {} by zero", lhs.kind())))
+            }
+            _ => arith(lhs, rhs, |a, b| (a % b == 0).then(|| a / b), |a, b| a / b)
+                .ok_or_else(|| cannot("divide", "by"))?,
+        },
+        Op::Rem => match (num(lhs), num(rhs)) {
+            (Some(_), Some(b)) if b.floor() == 0 => {
+                return Err(failed(format!("Can not divide {} by zero", lhs.kind())))
+            }
+            (Some(a), Some(b)) => int_value(a.floor() % b.floor()),
+            _ => return Err(cannot("divide", "by")),
+        },
+    })
+}
+
+/// Merges `b` into `a`, and tables in both into each other.
+fn merge(a: &Table, b: &Table) -> Table {
+    let mut out = a.clone();
+    for (key, val) in &b.items {
+        let merged = match (out.items.get(key), val) {
+            (Some(Value::Table(a)), Value::Table(b)) => Value::Table(merge(a, b)),
+            _ => val.clone(),
+        };
+        out.items.insert(key.clone(), merged);
+    }
+    out
+}
+
+/// The order of kinds of values in comparisons, as in jq.
+fn rank(val: &Value) -> u8 {
+    match val {
+        Value::Null => 0,
+        Value::Bool(false) => 1,
+        Value::Bool(true) => 2,
+        Value::Int(_) | Value::UInt(_) | Value::Float(_) => 3,
+        Value::String(_) | Value::Datetime(_) => 4,
+        Value::Array(_) => 5,
+        Value::Table(_) => 6,
+    }
+}
+
+/// Orders any two values: first by kind, then numbers by value, strings and
+/// datetimes by their text, arrays element by element and tables by their
+/// sorted keys and then their values.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        _ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Table(a), Value::Table(b)) => {
+            let mut keys: Vec<_> = a.items.keys().collect();
+            let mut others: Vec<_> = b.items.keys().collect();
+            keys.sort();
+            others.sort();
+            keys.cmp(&others).then_with(|| {
+                keys.iter()
+                    .map(|key| compare(&a.items[*key], &b.items[*key]))
+                    .find(|order| order.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => match (num(a), num(b)) {
+            (Some(Num::Int(a)), Some(Num::Int(b))) => a.cmp(&b),
+            (Some(a), Some(b)) => {
+                let (a, b) = (a.float(), b.float());
+                a.partial_cmp(&b)
+                    .unwrap_or_else(|| b.is_nan().cmp(&a.is_nan()))
+            }
+            _ => text(a).cmp(&text(b)),
+        },
+    }
+}
+
+fn text(val: &Value) -> Cow<'_, str> {
+    match val {
+        Value::String(s) => Cow::Borrowed(s),
+        Value::Datetime(dt) => Cow::Owned(dt.to_string()),
+        _ => Cow::Borrowed(""),
+    }
+}
+
+/***********************************************/
+// Functions
+
+/// The name of the kind of `val` as jq has it, plus `datetime`.
+fn type_name(val: &Value) -> &'static str {
+    match val {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Int(_) | Value::UInt(_) | Value::Float(_) => "number",
+        Value::String(_) => "string",
+        Value::Datetime(_) => "datetime",
+        Value::Array(_) => "array",
+        Value::Table(_) => "object",
+    }
+}
+
+fn needs(name: &str, what: &str, val: &Value) -> Error {
+    failed(format!("{name} needs {what}, not {}", val.kind()))
+}
+
+fn string<'a>(val: &'a Value, name: &str) -> Result<&'a str, Error> {
+    match val {
+        Value::String(s) => Ok(s),
+        val => Err(needs(name, "a string", val)),
+    }
+}
+
+fn array<'a>(val: &'a Value, name: &str) -> Result<&'a [Value], Error> {
+    match val {
+        Value::Array(a) => Ok(a),
+        val => Err(needs(name, "an array", val)),
+    }
+}
+
+fn number(val: &Value, name: &str) -> Result<Num, Error> {
+    num(val).ok_or_else(|| needs(name, "a number", val))
+}
+
+fn integer(val: &Value, name: &str) -> Result<i128, Error> {
+    match number(val, name)? {
+        Num::Int(i) => Ok(i),
+        Num::Float(f) if f.fract() == 0.0 => Ok(f as i128),
+        Num::Float(_) => Err(needs(name, "an integer", val)),
+    }
+}
+
+/// Runs `f` on each result of `arg`, for functions whose argument is a value.
+fn each(
+    arg: &Expr,
+    input: &Value,
+    f: impl Fn(&Value) -> Result<Value, Error>,
+) -> Result<Vec<Value>, Error> {
+    collect(arg, input)?.iter().map(f).collect()
+}
+
+/// The elements of `input` with `[f]` run on each, sorted by it.
+fn sorted_by(input: &Value, f: &Expr, name: &str) -> Result<Vec<(Value, Value)>, Error> {
+    let mut keyed = array(input, name)?
+        .iter()
+        .map(|val| Ok((Value::Array(collect(f, val)?), val.clone())))
+        .collect::<Result<Vec<_>, Error>>()?;
+    keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+    Ok(keyed)
+}
+
+fn call<'a>(name: &'a str, args: &'a [Expr], input: Rc<Value>) -> Results<'a> {
+    match (name, args) {
+        ("empty", []) => Box::new(iter::empty()),
+        ("error", [msg]) => then(eval(msg, input), |msg| fail(failed(message(&msg)))),
+        ("select", [f]) => Box::new(eval(f, input.clone()).filter_map(move |val| match val {
+            Ok(val) if !truthy(&val) => None,
+            Ok(_) => Some(Ok(Value::clone(&input))),
+            Err(err) => Some(Err(err)),
+        })),
+        ("recurse", []) => descend(input),
+        ("recurse", [f]) => recurse(f, Rc::unwrap_or_clone(input)),
+        ("first", [f]) => Box::new(eval(f, input).take(1)),
+        ("last", [f]) => many(
+            eval(f, input)
+                .try_fold(None, |_, val| val.map(Some))
+                .map(|last| last.into_iter().collect()),
+        ),
+        ("limit", [n, f]) => then(eval(n, input.clone()), move |n| match integer(&n, name) {
+            Ok(n) => Box::new(eval(f, input.clone()).take(usize::try_from(n).unwrap_or(0))),
+            Err(err) => fail(err),
+        }),
+        ("range", [to]) => then(eval(to, input), |to| range(0, &to)),
+        ("range", [from, to]) => then(eval(from, input.clone()), move |from| {
+            match integer(&from, name) {
+                Ok(from) => then(eval(to, input.clone()), move |to| range(from, &to)),
+                Err(err) => fail(err),
+            }
+        }),
+        _ => later(move || many(apply(name, args, &input))),
+    }
+}
+
+fn range<'a>(from: i128, to: &Value) -> Results<'a> {
+    match integer(to, "range") {
+        Ok(to) => Box::new((from..to).map(|i| Ok(int_value(i)))),
+        Err(err) => fail(err),
+    }
+}
+
+/// Calls `name`, for the functions with a result for each result of their
+/// arguments, or with one result for all of them.
+fn apply(name: &str, args: &[Expr], input: &Value) -> Result<Vec<Value>, Error> {
+    let one = |val: Value| Ok(vec![val]);
+    match (name, args) {
+        ("error", []) => Err(failed(message(input))),
+        ("not", []) => one(Value::Bool(!truthy(input))),
+        ("length", []) => one(length(input)?),
+        ("type", []) => one(Value::String(type_name(input).to_owned())),
+        ("keys" | "keys_unsorted", []) => one(match input {
+            Value::Table(t) => {
+                let mut keys: Vec<_> = t.items.keys().cloned().collect();
+                if name == "keys" {
+                    keys.sort();
+                }
+                Value::Array(keys.into_iter().map(Value::String).collect())
+            }
+            Value::Array(a) => Value::Array((0..a.len()).map(|i| int_value(i as i128)).collect()),
+            val => return Err(needs(name, "a table or an array", val)),
+        }),
+        ("has", [key]) => each(key, input, |key| match (input, key) {
+            (Value::Table(t), Value::String(key)) => Ok(Value::Bool(t.items.contains_key(key))),
+            (Value::Array(a), key) if num(key).is_some() => {
+                let i = num(key).map_or(-1, Num::floor);
+                Ok(Value::Bool(0 <= i && i < a.len() as i128))
+            }
+            _ => Err(failed(format!(
+                "Can not check whether {} has {}",
+                input.kind(),
+                describe(key)
+            ))),
+        }),
+        ("contains", [other]) => each(other, input, |other| {
+            contains(input, other).map(Value::Bool)
+        }),
+        ("add", []) => match input {
+            Value::Null => one(Value::Null),
+            input => one(iterate(input.clone())?
+                .iter()
+                .try_fold(Value::Null, |sum, val| binary(Op::Add, &sum, val))?),
+        },
+        ("any" | "all", []) => {
+            let a = array(input, name)?;
+            one(Value::Bool(match name {
+                "any" => a.iter().any(truthy),
+                _ => a.iter().all(truthy),
+            }))
+        }
+        ("any" | "all", [f]) => {
+            let all = name == "all";
+            for val in iterate(input.clone())? {
+                let found = eval(f, Rc::new(val))
+                    .find_map(|val| match val {
+                        Ok(val) if !truthy(&val) => None,
+                        val => Some(val),
+                    })
+                    .transpose()?;
+                if found.is_some() != all {
+                    return one(Value::Bool(!all));
+                }
+            }
+            one(Value::Bool(all))
+        }
+        ("flatten", []) => one(flatten(array(input, name)?, usize::MAX)),
+        ("flatten", [depth]) => each(depth, input, |depth| {
+            let depth = usize::try_from(integer(depth, name)?)
+                .map_err(|_| failed("flatten needs a depth of 0 or more"))?;
+            Ok(flatten(array(input, name)?, depth))
+        }),
+        ("map", [f]) => {
+            let mut out = vec![];
+            for val in iterate(input.clone())? {
+                out.extend(collect(f, &val)?);
+            }
+            one(Value::Array(out))
+        }
+        ("map_values", [f]) => {
+            let every = Expr::Iterate(Box::new(Expr::Identity));
+            one(update(&every, f, input)?)
+        }
+        (name, []) if selects(name, input).is_some() => match selects(name, input) {
+            Some(true) => one(input.clone()),
+            _ => Ok(vec![]),
+        },
+        ("first", []) => one(index_value(input, &Value::UInt(0))?),
+        ("last", []) => one(index_value(input, &Value::Int(-1))?),
+        ("to_entries", []) => one(Value::Array(to_entries(input)?)),
+        ("from_entries", []) => one(from_entries(array(input, name)?)?),
+        ("with_entries", [f]) => {
+            let mut entries = vec![];
+            for entry in to_entries(input)? {
+                entries.extend(collect(f, &entry)?);
+            }
+            one(from_entries(&entries)?)
+        }
+        ("del", [f]) => {
+            let mut out = input.clone();
+            let paths = paths(f, &Path::new(), input)?;
+            delete(&mut out, paths.into_iter().map(|(path, _)| path).collect());
+            one(out)
+        }
+        ("path", [f]) => Ok(paths(f, &Path::new(), input)?
+            .iter()
+            .map(|(path, _)| path_value(path))
+            .collect()),
+        ("paths", []) => {
+            let mut found = vec![];
+            descendants(&Path::new(), input, &mut found);
+            Ok(found
+                .iter()
+                .skip(1)
+                .map(|(path, _)| path_value(path))
+                .collect())
+        }
+        ("sort", []) => {
+            let mut a = array(input, name)?.to_vec();
+            a.sort_by(compare);
+            one(Value::Array(a))
+        }
+        ("sort_by", [f]) => one(Value::Array(
+            sorted_by(input, f, name)?
+                .into_iter()
+                .map(|(_, val)| val)
+                .collect(),
+        )),
+        ("group_by", [f]) => {
+            let mut groups: Vec<(Value, Vec<Value>)> = vec![];
+            for (key, val) in sorted_by(input, f, name)? {
+                match groups.last_mut() {
+                    Some((last, group)) if compare(last, &key).is_eq() => group.push(val),
+                    _ => groups.push((key, vec![val])),
+                }
+            }
+            one(Value::Array(
+                groups
+                    .into_iter()
+                    .map(|(_, group)| Value::Array(group))
+                    .collect(),
+            ))
+        }
+        ("unique", []) => {
+            let mut a = array(input, name)?.to_vec();
+            a.sort_by(compare);
+            a.dedup_by(|a, b| compare(a, b).is_eq());
+            one(Value::Array(a))
+        }
+        ("unique_by", [f]) => {
+            let mut keyed = sorted_by(input, f, name)?;
+            keyed.dedup_by(|(a, _), (b, _)| compare(a, b).is_eq());
+            one(Value::Array(
+                keyed.into_iter().map(|(_, val)| val).collect(),
+            ))
+        }
+        ("min", []) => one(array(input, name)?
+            .iter()
+            .min_by(|a, b| compare(a, b))
+            .cloned()
+            .unwrap_or_default()),
+        ("max", []) => one(array(input, name)?
+            .iter()
+            .max_by(|a, b| compare(a, b))
+            .cloned()
+            .unwrap_or_default()),
+        ("min_by" | "max_by", [f]) => {
+            let keyed = sorted_by(input, f, name)?;
+            let found = match name {
+                "min_by" => keyed.into_iter().next(),
+                _ => keyed.into_iter().last(),
+            };
+            one(found.map(|(_, val)| val).unwrap_or_default())
+        }
+        ("reverse", []) => one(match input {
+            Value::Array(a) => Value::Array(a.iter().rev().cloned().collect()),
+            Value::String(s) => Value::String(s.chars().rev().collect()),
+            Value::Null => Value::Array(vec![]),
+            val => return Err(needs(name, "an array or a string", val)),
+        }),
+        ("floor" | "ceil" | "round", []) => one(match number(input, name)? {
+            Num::Int(i) => int_value(i),
+            Num::Float(f) => whole(match name {
+                "floor" => f.floor(),
+                "ceil" => f.ceil(),
+                _ => f.round(),
+            }),
+        }),
+        ("abs", []) => one(match number(input, name)? {
+            Num::Int(i) => int_value(i.abs()),
+            Num::Float(f) => Value::Float(f.abs()),
+        }),
+        ("sqrt", []) => one(Value::Float(number(input, name)?.float().sqrt())),
+        ("log", []) => one(Value::Float(number(input, name)?.float().ln())),
+        ("pow", [base, exp]) => {
+            let mut out = vec![];
+            let exps = collect(exp, input)?;
+            for base in collect(base, input)? {
+                for exp in &exps {
+                    out.push(match (number(&base, name)?, number(exp, name)?) {
+                        (Num::Int(b), Num::Int(e)) if (0..=u32::MAX.into()).contains(&e) => {
+                            match b.checked_pow(e as u32) {
+                                Some(i) => int_value(i),
+                                None => Value::Float((b as f64).powf(e as f64)),
+                            }
+                        }
+                        (b, e) => Value::Float(b.float().powf(e.float())),
+                    });
+                }
+            }
+            Ok(out)
+        }
+        ("tostring", []) => one(Value::String(message(input))),
+        ("tonumber", []) => one(match input {
+            Value::String(s) => match (s.trim().parse::<i128>(), s.trim().parse::<f64>()) {
+                (Ok(i), _) => int_value(i),
+                (_, Ok(f)) => Value::Float(f),
+                _ => return Err(failed(format!("Can not read {s:?} as a number"))),
+            },
+            val if num(val).is_some() => val.clone(),
+            val => return Err(needs(name, "a string or a number", val)),
+        }),
+        ("ascii_downcase", []) => one(Value::String(string(input, name)?.to_ascii_lowercase())),
+        ("ascii_upcase", []) => one(Value::String(string(input, name)?.to_ascii_uppercase())),
+        ("trim", []) => one(Value::String(string(input, name)?.trim().to_owned())),
+        ("ltrimstr" | "rtrimstr", [affix]) => each(affix, input, |affix| {
+            Ok(match (input, affix) {
+                (Value::String(s), Value::String(affix)) => Value::String(
+                    match name {
+                        "ltrimstr" => s.strip_prefix(affix.as_str()),
+                        _ => s.strip_suffix(affix.as_str()),
+                    }
+                    .unwrap_or(s)
+                    .to_owned(),
+                ),
+                _ => input.clone(),
+            })
+        }),
+        ("startswith" | "endswith", [affix]) => each(affix, input, |affix| {
+            let (s, affix) = (string(input, name)?, string(affix, name)?);
+            Ok(Value::Bool(match name {
+                "startswith" => s.starts_with(affix),
+                _ => s.ends_with(affix),
+            }))
+        }),
+        ("split", [sep]) => each(sep, input, |sep| {
+            Ok(split(string(input, name)?, string(sep, name)?))
+        }),
+        ("join", [sep]) => each(sep, input, |sep| {
+            let sep = string(sep, name)?;
+            let parts = array(input, name)?
+                .iter()
+                .map(|val| match val {
+                    Value::Null => Ok(String::new()),
+                    Value::Array(_) | Value::Table(_) => {
+                        Err(failed(format!("Can not join {}", val.kind())))
+                    }
+                    val => Ok(message(val)),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(Value::String(parts.join(sep)))
+        }),
+        _ => Err(failed(format!("unknown function {name}/{}", args.len()))),
+    }
+}
+
+/// Whether the selector `name`, like `strings`, keeps `val`, or `None` if
+/// `name` is not a selector.
+fn selects(name: &str, val: &Value) -> Option<bool> {
+    let kind = type_name(val);
+    Some(match name {
+        "values" => kind != "null",
+        "nulls" => kind == "null",
+        "booleans" => kind == "boolean",
+        "numbers" => kind == "number",
+        "strings" => kind == "string",
+        "arrays" => kind == "array",
+        "objects" => kind == "object",
+        "iterables" => matches!(kind, "array" | "object"),
+        "scalars" => !matches!(kind, "array" | "object"),
+        _ => return None,
+    })
+}
+
+/// Strings as they are, and other values as they are displayed.
+fn message(val: &Value) -> String {
+    match val {
+        Value::String(s) => s.clone(),
+        val => val.to_string(),
+    }
+}
+
+fn length(val: &Value) -> Result<Value, Error> {
+    Ok(match val {
+        Value::Null => Value::UInt(0),
+        Value::String(s) => int_value(s.chars().count() as i128),
+        Value::Array(a) => int_value(a.len() as i128),
+        Value::Table(t) => int_value(t.items.len() as i128),
+        val => match num(val) {
+            Some(Num::Int(i)) => int_value(i.abs()),
+            Some(Num::Float(f)) => Value::Float(f.abs()),
+            None => return Err(failed(format!("Can not take the length of {}", val.kind()))),
+        },
+    })
+}
+
+/// Whether `a` has everything in `b`: substrings for strings, and for arrays
+/// and tables values that contain the ones in `b`.
+fn contains(a: &Value, b: &Value) -> Result<bool, Error> {
+    Ok(match (a, b) {
+        (Value::String(a), Value::String(b)) => a.contains(b.as_str()),
+        (Value::Array(a), Value::Array(b)) => b
+            .iter()
+            .all(|b| a.iter().any(|a| contains(a, b).unwrap_or(false))),
+        (Value::Table(a), Value::Table(b)) => b.items.iter().all(|(key, b)| {
+            a.items
+                .get(key)
+                .is_some_and(|a| contains(a, b).unwrap_or(false))
+        }),
+        _ if type_name(a) == type_name(b) => compare(a, b).is_eq(),
+        _ => {
+            return Err(failed(format!(
+                "Can not check whether {} contains {}",
+                a.kind(),
+                b.kind()
+            )))
+        }
+    })
+}
+
+fn flatten(a: &[Value], depth: usize) -> Value {
+    let mut out = vec![];
+    for val in a {
+        match val {
+            Value::Array(inner) if depth > 0 => match flatten(inner, depth - 1) {
+                Value::Array(inner) => out.extend(inner),
+                _ => unreachable!("flatten returns an array"),
+            },
+            val => out.push(val.clone()),
+        }
+    }
+    Value::Array(out)
+}
+
+/// `val`, then what `f` makes of it, then what `f` makes of those and so on,
+/// depth first. The results still to visit are kept on a stack of their own,
+/// so that `recurse` can go deep or forever without overflowing the stack.
+fn recurse(f: &Expr, val: Value) -> Results<'_> {
+    let mut stack = vec![one(val)];
+    Box::new(iter::from_fn(move || loop {
+        match stack.last_mut()?.next() {
+            Some(Ok(val)) => {
+                stack.push(eval(f, Rc::new(val.clone())));
+                return Some(Ok(val));
+            }
+            Some(Err(err)) => {
+                stack.clear();
+                return Some(Err(err));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }))
+}
+
+fn split(s: &str, sep: &str) -> Value {
+    let parts: Vec<Value> = match (s.is_empty(), sep.is_empty()) {
+        (true, _) => vec![],
+        (false, true) => s.chars().map(|c| Value::String(c.into())).collect(),
+        (false, false) => s
+            .split(sep)
+            .map(|part| Value::String(part.to_owned()))
+            .collect(),
+    };
+    Value::Array(parts)
+}
+
+fn to_entries(val: &Value) -> Result<Vec<Value>, Error> {
+    match val {
+        Value::Table(t) => Ok(t
+            .items
+            .iter()
+            .map(|(key, val)| {
+                Value::Table(Table::new([
+                    ("key".to_owned(), Value::String(key.clone())),
+                    ("value".to_owned(), val.clone()),
+                ]))
+            })
+            .collect()),
+        val => Err(needs("to_entries", "a table", val)),
+    }
+}
+
+/// A table from `{key, value}` tables, which may also name their key `k`
+/// or `name` and their value `v`.
+fn from_entries(entries: &[Value]) -> Result<Value, Error> {
+    let mut out = Table::default();
+    for entry in entries {
+        let Value::Table(entry) = entry else {
+            return Err(needs("from_entries", "tables", entry));
+        };
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| entry.items.get(*name).filter(|val| truthy(val)))
+        };
+        let key = match field(&["key", "k", "name"]) {
+            Some(Value::Array(_) | Value::Table(_)) | None => {
+                return Err(failed("from_entries needs a string key in every entry"))
+            }
+            Some(key) => message(key),
+        };
+        let val = field(&["value", "v"]).cloned().unwrap_or_default();
+        out.items.insert(key, val);
+    }
+    Ok(Value::Table(out))
+}
@@ -0,0 +1,112 @@
+use std::error::Error as StdError;
+use std::sync::{Arc, RwLock};
+
+use crate::format::Format;
+use crate::style::StyleOptions;
+use crate::value::Value;
+
+/// The errors of [`FileFormat`]s, which each have their own.
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// A format added to `truns` from outside, next to the built-in ones.
+///
+/// Once [`register`]ed, it is [`Format::Custom`] with its name, and can be
+/// used with [`Table::parse`](crate::Table::parse),
+/// [`Table::to_string_with`](crate::Table::to_string_with), detection by
+/// file extension and `--from`/`--to` style names.
+///
+/// The built-in formats are not `FileFormat`s: they keep their own code in
+/// `Table`, `Value` and the parser, as they do more than this trait can
+/// express. A registered format, by comparison:
+///
+/// - holds one document, never a stream;
+/// - has parse errors without a line and column;
+/// - is only guessed by [`detect::sniff`](crate::detect::sniff) after every
+///   built-in format, with low confidence;
+/// - gets values as they are, so [`Options`](crate::Options) do not apply
+///   and its [`ConversionReport`](crate::ConversionReport) only lists the
+///   comments it drops.
+pub trait FileFormat: Send + Sync {
+    /// The name the format goes by, like `toml`.
+    fn name(&self) -> &'static str;
+    /// File extensions, without the dot and in lowercase.
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+    /// Reads a document, which has a table at the top.
+    fn parse(&self, input: &[u8]) -> Result<Value, BoxError>;
+    /// Writes a document, laid out as much as the format allows like
+    /// `style` asks.
+    fn emit(&self, value: &Value, style: &StyleOptions) -> Result<Vec<u8>, BoxError>;
+}
+
+/// What a format can hold, for picking one. Whatever a conversion loses is
+/// in its [`ConversionReport`](crate::ConversionReport) either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub null: bool,
+    pub datetimes: bool,
+    /// NaN and infinite floats.
+    pub non_finite_floats: bool,
+    /// Arrays, and tables in tables. INI has only one level of sections.
+    pub nesting: bool,
+    /// Comments can be kept in conversions to the format.
+    pub comments: bool,
+    /// One text can hold several documents.
+    pub multiple_documents: bool,
+}
+
+impl Capabilities {
+    /// The names of the capabilities the format has, for listing.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.null, "null"),
+            (self.datetimes, "datetimes"),
+            (self.non_finite_floats, "non-finite floats"),
+            (self.nesting, "nesting"),
+            (self.comments, "comments"),
+            (self.multiple_documents, "multiple documents"),
+        ]
+        .into_iter()
+        .filter_map(|(has, name)| has.then_some(name))
+        .collect()
+    }
+}
+
+static FORMATS: RwLock<Vec<Arc<dyn FileFormat>>> = RwLock::new(Vec::new());
+
+/// Adds `format` for the rest of the process, replacing a registered format
+/// of the same name, and returns the [`Format`] to use it by.
+///
+/// # Panics
+///
+/// If the name is taken by a built-in format.
+pub fn register(format: impl FileFormat + 'static) -> Format {
+    let name = format.name();
+    assert!(
+        !Format::builtin().iter().any(|f| f.name() == name),
+        "the format name '{name}' is taken by a built-in format"
+    );
+    let mut formats = FORMATS.write().unwrap_or_else(|e| e.into_inner());
+    formats.retain(|f| f.name() != name);
+    formats.push(Arc::new(format));
+    Format::Custom(name)
+}
+
+/// The registered format named `name`.
+pub fn get(name: &str) -> Option<Arc<dyn FileFormat>> {
+    let formats = FORMATS.read().unwrap_or_else(|e| e.into_inner());
+    formats.iter().find(|f| f.name() == name).cloned()
+}
+
+/// Every format: the built-in ones, then the registered ones in the order
+/// they were added.
+pub fn formats() -> Vec<Format> {
+    let formats = FORMATS.read().unwrap_or_else(|e| e.into_inner());
+    let mut out = Format::builtin();
+    out.extend(formats.iter().map(|f| Format::Custom(f.name())));
+    out
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::format::Format;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::options::Options;
+use crate::path::{Path, Segment};
+
+/// Everything a conversion had to change to fit the target format.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConversionReport {
+    pub entries: Vec<Lossy>,
+}
+
+impl ConversionReport {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Fails with [`Error::Lossy`] if anything was changed, for callers that
+    /// only accept exact conversions.
+    pub fn strict(self, format: Format) -> Result<(), Error> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Lossy {
+                format,
+                report: self,
+            }),
+        }
+    }
+
+    /// Adds the entries of a report for the value at `segment`.
+    pub fn extend_at(&mut self, segment: Segment, other: ConversionReport) {
+        for mut entry in other.entries {
+            entry.path.prepend(segment.clone());
+            self.entries.push(entry);
+        }
+    }
+}
+
+/// A single value that was not carried over as-is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lossy {
+    pub path: Path,
+    pub kind: LossKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LossKind {
+    /// An unsigned integer above `i64::MAX`, written as a string.
+    UIntAsString(u64),
+    /// An unsigned integer above `i64::MAX`, written as the nearest float.
+    UIntAsFloat(u64),
+    /// A null left out of a format that has none.
+    NullDropped,
+    /// A null written as this string.
+    NullReplaced(String),
+    /// A datetime written as a string, in a format without datetimes or
+    /// with `DatetimeStyle::String`.
+    DatetimeAsString,
+    /// A NaN or infinite float written as null.
+    NonFiniteAsNull(f64),
+    /// A NaN or infinite float written as a string.
+    NonFiniteAsString(f64),
+    /// A typed scalar, like an integer, written as a string in INI.
+    ScalarAsString(&'static str),
+    /// A key that was not a string in the source, like the YAML `1: one`.
+    KeyAsString,
+    /// An array written as a key repeated once per element, in INI.
+    ArrayAsRepeatedKeys,
+    /// A table nested in an INI section, written as a dotted section.
+    TableAsSection,
+    /// A value of this type left out of the output.
+    Dropped(&'static str),
+    /// A key that would read back as another key, or not at all, left out
+    /// with its value.
+    KeyDropped(&'static str),
+    /// A string with whitespace at either end, which INI trims.
+    Trimmed,
+    /// A string starting with a quote, which INI reads as quoted.
+    LeadingQuote,
+    /// A value written ahead of the tables that came before it, since TOML
+    /// puts the values of a table before the tables in it.
+    MovedBeforeTables,
+}
+
+impl fmt::Display for Lossy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            LossKind::UIntAsString(i) => {
+                write!(
+                    f,
+                    "{i} does not fit in a signed 64-bit integer, written as a string"
+                )
+            }
+            LossKind::UIntAsFloat(i) => write!(
+                f,
+                "{i} does not fit in a signed 64-bit integer, written as the float {}",
+                *i as f64
+            ),
+            LossKind::NullDropped => f.write_str("null left out"),
+            LossKind::NullReplaced(s) => write!(f, "null written as {s:?}"),
+            LossKind::DatetimeAsString => f.write_str("datetime written as a string"),
+            LossKind::NonFiniteAsNull(x) => write!(f, "{x} written as null"),
+            LossKind::NonFiniteAsString(x) => write!(f, "{x} written as a string"),
+            LossKind::ScalarAsString(name) => write!(f, "{name} written as a string"),
+            LossKind::KeyAsString => f.write_str("non-string key written as a string"),
+            LossKind::ArrayAsRepeatedKeys => f.write_str("array written as repeated keys"),
+            LossKind::TableAsSection => f.write_str("nested table written as a dotted section"),
+            LossKind::Dropped(name) => write!(f, "{name} left out"),
+            LossKind::KeyDropped(why) => write!(f, "{why}, left out with its value"),
+            LossKind::Trimmed => f.write_str("whitespace at the ends trimmed when read back"),
+            LossKind::LeadingQuote => f.write_str("leading quote read back as quoting"),
+            LossKind::MovedBeforeTables => f.write_str("moved ahead of the tables before it"),
+        }
+    }
+}
+
+/// State threaded through a conversion: the settings, where in the document
+/// it is, and what has been lost so far.
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+pub(crate) struct Ctx<'a> {
+    #[cfg_attr(
+        not(any(feature = "json", feature = "toml", feature = "yaml")),
+        allow(dead_code)
+    )]
+    pub options: &'a Options,
+    pub path: Path,
+    pub report: ConversionReport,
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+impl<'a> Ctx<'a> {
+    pub fn new(options: &'a Options) -> Self {
+        Self {
+            options,
+            path: Path::new(),
+            report: ConversionReport::default(),
+        }
+    }
+
+    pub fn lossy(&mut self, kind: LossKind) {
+        self.report.entries.push(Lossy {
+            path: self.path.clone(),
+            kind,
+        });
+    }
+
+    /// Runs `f` with `segment` appended to the current path.
+    pub fn at<T>(&mut self, segment: Segment, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(segment);
+        let out = f(self);
+        self.path.pop();
+        out
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::format::Format;
+use crate::options::Options;
+use crate::path::{Path, Segment};
+use crate::style::StyleOptions;
+use crate::table::Table;
+use crate::value::{IniOptions, Value};
+
+/// A value that did not survive a round trip unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    pub path: Path,
+    pub kind: DiffKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffKind {
+    /// In the original, gone after the round trip.
+    Missing(Value),
+    /// Not in the original, there after the round trip.
+    Added(Value),
+    Changed(Value, Value),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            DiffKind::Missing(val) => write!(f, "{val} went missing"),
+            DiffKind::Added(val) => write!(f, "{val} was added"),
+            DiffKind::Changed(before, after) => write!(f, "{before} became {after}"),
+        }
+    }
+}
+
+/// Writes `table` as `via` and reads it back, then does the same with `from`,
+/// and compares the result with `table`.
+///
+/// An empty result means the document survives `from` → `via` → `from`
+/// unchanged. Values the options say to fail on are errors, as in a plain
+/// conversion. Only the `via` text is written in `style`.
+pub fn check(
+    table: &Table,
+    from: Format,
+    via: Format,
+    options: &Options,
+    ini: &IniOptions,
+    style: &StyleOptions,
+) -> Result<Vec<Difference>, Error> {
+    let there = hop(table.clone(), via, options, ini, style)?;
+    let back = hop(there, from, options, ini, &StyleOptions::default())?;
+    Ok(diff(table, &back))
+}
+
+fn hop(
+    table: Table,
+    format: Format,
+    options: &Options,
+    ini: &IniOptions,
+    style: &StyleOptions,
+) -> Result<Table, Error> {
+    let (text, _) = table.to_string_with(format, options, ini, style)?;
+    Table::parse_with(&text, format, ini)
+}
+
+/// Compares two tables the way a reader of the documents would: key order
+/// does not matter, integers compare by value whatever their signedness and
+/// NaN equals NaN.
+pub fn diff(before: &Table, after: &Table) -> Vec<Difference> {
+    let mut out = vec![];
+    diff_tables(before, after, &mut Path::new(), &mut out);
+    out
+}
+
+fn diff_tables(before: &Table, after: &Table, path: &mut Path, out: &mut Vec<Difference>) {
+    for (key, val) in &before.items {
+        path.push(Segment::Key(key.clone()));
+        match after.items.get(key) {
+            Some(other) => diff_values(val, other, path, out),
+            None => out.push(difference(path, DiffKind::Missing(val.clone()))),
+        }
+        path.pop();
+    }
+    for (key, val) in &after.items {
+        if !before.items.contains_key(key) {
+            path.push(Segment::Key(key.clone()));
+            out.push(difference(path, DiffKind::Added(val.clone())));
+            path.pop();
+        }
+    }
+}
+
+fn diff_values(before: &Value, after: &Value, path: &mut Path, out: &mut Vec<Difference>) {
+    match (before, after) {
+        (Value::Table(a), Value::Table(b)) => diff_tables(a, b, path, out),
+        (Value::Array(a), Value::Array(b)) => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                path.push(Segment::Index(i));
+                diff_values(a, b, path, out);
+                path.pop();
+            }
+            for (i, val) in a.iter().enumerate().skip(b.len()) {
+                path.push(Segment::Index(i));
+                out.push(difference(path, DiffKind::Missing(val.clone())));
+                path.pop();
+            }
+            for (i, val) in b.iter().enumerate().skip(a.len()) {
+                path.push(Segment::Index(i));
+                out.push(difference(path, DiffKind::Added(val.clone())));
+                path.pop();
+            }
+        }
+        (a, b) if same_scalar(a, b) => {}
+        (a, b) => out.push(difference(path, DiffKind::Changed(a.clone(), b.clone()))),
+    }
+}
+
+fn same_scalar(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(i), Value::UInt(u)) | (Value::UInt(u), Value::Int(i)) => {
+            u64::try_from(*i) == Ok(*u)
+        }
+        (Value::Float(a), Value::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+        _ => a == b,
+    }
+}
+
+fn difference(path: &Path, kind: DiffKind) -> Difference {
+    Difference {
+        path: path.clone(),
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(items: impl IntoIterator<Item = (&'static str, Value)>) -> Table {
+        Table::new(
+            items
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v))
+                .collect::<indexmap::IndexMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn ignores_key_order_and_integer_signedness() {
+        let a = table([("a", Value::UInt(1)), ("b", Value::Float(f64::NAN))]);
+        let b = table([("b", Value::Float(f64::NAN)), ("a", Value::Int(1))]);
+        assert_eq!(diff(&a, &b), vec![]);
+    }
+
+    #[test]
+    fn reports_paths_of_changes() {
+        let a = table([(
+            "servers",
+            Value::Array(vec![Value::Table(table([("port", Value::UInt(80))]))]),
+        )]);
+        let b = table([(
+            "servers",
+            Value::Array(vec![
+                Value::Table(table([("port", Value::String("80".to_owned()))])),
+                Value::Null,
+            ]),
+        )]);
+        let diffs: Vec<_> = diff(&a, &b).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            diffs,
+            [
+                r#"servers[0].port: 80 became "80""#,
+                "servers[1]: null was added"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_keys() {
+        let a = table([("a", Value::Null), ("b", Value::Bool(true))]);
+        let b = table([("b", Value::Bool(true))]);
+        assert_eq!(
+            diff(&a, &b),
+            vec![Difference {
+                path: {
+                    let mut path = Path::new();
+                    path.push(Segment::Key("a".to_owned()));
+                    path
+                },
+                kind: DiffKind::Missing(Value::Null),
+            }]
+        );
+    }
+}
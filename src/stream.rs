@@ -0,0 +1,208 @@
+#[cfg(feature = "yaml")]
+use yaml_rust::Yaml;
+
+use crate::comment;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::comment::Comments;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::error::Error;
+use crate::format::Format;
+#[cfg(feature = "json")]
+use crate::json5;
+use crate::options::LoadOptions;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::options::Options;
+#[cfg(feature = "json")]
+use crate::path::Path;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::path::Segment;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::report::ConversionReport;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use crate::style::StyleOptions;
+use crate::table::Table;
+#[cfg(feature = "yaml")]
+use crate::value::KeyPolicy;
+#[cfg(feature = "json")]
+use crate::value::Value;
+#[cfg(feature = "yaml")]
+use crate::yaml_emitter;
+
+/// Several documents in a row, like a YAML stream of `---` separated
+/// documents or a JSON Lines file.
+///
+/// Paths in the [`ConversionReport`]s of a stream with more than one document
+/// start with the index of the document, as in `[2].metadata.name`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stream {
+    pub documents: Vec<Table>,
+}
+
+impl From<Table> for Stream {
+    fn from(table: Table) -> Self {
+        Self {
+            documents: vec![table],
+        }
+    }
+}
+
+impl Stream {
+    pub fn new(documents: Vec<Table>) -> Self {
+        Self { documents }
+    }
+
+    pub fn sort_keys(&mut self) {
+        self.documents.iter_mut().for_each(Table::sort_keys);
+    }
+
+    /// Reads the comments of `text`, the source of the stream, into its
+    /// documents. See [`Table::attach_comments`].
+    pub fn attach_comments(&mut self, text: &str, format: Format, yaml: &LoadOptions) {
+        let comments = comment::read(text, format, yaml);
+        for (table, comments) in self.documents.iter_mut().zip(comments) {
+            table.attach_comments(comments);
+        }
+    }
+
+    /// Converts each document with `f`, collecting the reports.
+    #[cfg(any(feature = "json", feature = "yaml"))]
+    fn convert<T>(
+        self,
+        mut f: impl FnMut(Table) -> Result<(T, ConversionReport), Error>,
+    ) -> Result<(Vec<T>, ConversionReport), Error> {
+        let nested = self.documents.len() > 1;
+        let mut report = ConversionReport::default();
+        let mut out = Vec::with_capacity(self.documents.len());
+        for (i, table) in self.documents.into_iter().enumerate() {
+            let (doc, doc_report) = f(table).map_err(|e| e.in_document(i))?;
+            if nested {
+                report.extend_at(Segment::Index(i), doc_report);
+            } else {
+                report.entries.extend(doc_report.entries);
+            }
+            out.push(doc);
+        }
+        Ok((out, report))
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(documents: Vec<Yaml>) -> Result<Self, Error> {
+        Self::from_yaml_with(documents, KeyPolicy::default())
+    }
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_with(documents: Vec<Yaml>, keys: KeyPolicy) -> Result<Self, Error> {
+        documents
+            .into_iter()
+            .enumerate()
+            .map(|(i, doc)| Table::from_yaml_with(doc, keys).map_err(|e| e.in_document(i)))
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+    }
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml_with(self, options: &Options) -> Result<(Vec<Yaml>, ConversionReport), Error> {
+        self.convert(|table| table.to_yaml_with(options))
+    }
+    /// Writes the documents as one YAML stream, each starting with `---`.
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml_string(
+        self,
+        options: &Options,
+        style: &StyleOptions,
+    ) -> Result<(String, ConversionReport), Error> {
+        let nested = self.documents.len() > 1;
+        let comments: Vec<Comments> = self
+            .documents
+            .iter()
+            .map(|table| table.all_comments().into_iter().collect())
+            .collect();
+        let (docs, mut report) = self.to_yaml_with(options)?;
+        let mut out = String::new();
+        for (i, (doc, comments)) in docs.iter().zip(&comments).enumerate() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let (text, dropped) = yaml_emitter::emit(doc, comments, style);
+            match nested {
+                true => report.extend_at(Segment::Index(i), dropped),
+                false => report.entries.extend(dropped.entries),
+            }
+            out.push_str(&text);
+        }
+        style.end(&mut out);
+        Ok((out, report))
+    }
+
+    /// Reads a JSON array with one document per element.
+    #[cfg(feature = "json")]
+    pub fn from_json_array(content: serde_json::Value) -> Result<Self, Error> {
+        let serde_json::Value::Array(docs) = content else {
+            return Err(Error::invalid(
+                Format::Json,
+                &Path::new(),
+                "expected an array of documents",
+            ));
+        };
+        Self::from_json_documents(docs, Format::Json)
+    }
+    /// Takes each of `docs`, read as `format`, as a document.
+    #[cfg(feature = "json")]
+    fn from_json_documents(docs: Vec<serde_json::Value>, format: Format) -> Result<Self, Error> {
+        docs.into_iter()
+            .enumerate()
+            .map(|(i, doc)| Table::from_json(doc).ok_or(Error::NotATable(format).in_document(i)))
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+    }
+    #[cfg(feature = "json")]
+    pub fn to_json_array(
+        self,
+        options: &Options,
+    ) -> Result<(serde_json::Value, ConversionReport), Error> {
+        let (docs, report) = self.convert(|table| table.to_json_with(options))?;
+        Ok((serde_json::Value::Array(docs), report))
+    }
+
+    /// Writes the documents as text of a JSON array, laid out as `style`
+    /// asks.
+    #[cfg(feature = "json")]
+    pub fn to_json_array_string(
+        self,
+        options: &Options,
+        style: &StyleOptions,
+    ) -> Result<(String, ConversionReport), Error> {
+        let mut comments = Comments::new();
+        for (i, table) in self.documents.iter().enumerate() {
+            for (mut path, comment) in table.all_comments() {
+                path.prepend(Segment::Index(i));
+                comments.insert(path, comment);
+            }
+        }
+        let docs = self.documents.into_iter().map(Value::Table).collect();
+        let (mut out, report) =
+            json5::write(&Value::Array(docs), Format::Json, options, style, &comments)?;
+        style.end(&mut out);
+        Ok((out, report))
+    }
+
+    /// Takes each line of JSON Lines, as read by
+    /// [`parse::json_lines`](crate::parse::json_lines), as a document.
+    #[cfg(feature = "json")]
+    pub fn from_json_lines(lines: Vec<serde_json::Value>) -> Result<Self, Error> {
+        Self::from_json_documents(lines, Format::JsonLines)
+    }
+    #[cfg(feature = "json")]
+    pub fn to_json_lines(
+        self,
+        options: &Options,
+        style: &StyleOptions,
+    ) -> Result<(String, ConversionReport), Error> {
+        let (docs, report) = self.convert(|table| table.to_json_with(options))?;
+        let mut out = String::new();
+        for doc in docs {
+            out.push_str(&doc.to_string());
+            out.push('\n');
+        }
+        style.end(&mut out);
+        Ok((out, report))
+    }
+}
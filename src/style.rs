@@ -0,0 +1,140 @@
+use std::str::FromStr;
+
+/// How text output is laid out. Nothing here changes what the text reads
+/// back as, only how it looks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyleOptions {
+    /// Spaces per level of nesting. `None` uses the usual width for the
+    /// format: four for the elements of TOML arrays, two everywhere else.
+    /// YAML needs at least one space to nest, so it gets one for `0`.
+    pub indent: Option<usize>,
+    /// Arrays and tables that fit in this many columns, indentation
+    /// included, are written on one line. `None` writes every element on a
+    /// line of its own.
+    pub line_width: Option<usize>,
+    /// Write everything on as few lines as possible: JSON without any
+    /// whitespace, YAML as a single flow collection and TOML arrays on one
+    /// line. Comments can not be written on one line and are left out.
+    pub compact: bool,
+    /// End the output with a newline.
+    pub trailing_newline: bool,
+    pub key_quotes: KeyQuotes,
+    pub string_quotes: StringQuotes,
+    pub yaml: YamlStyle,
+    /// TOML tables with at most this many entries, none of them tables or
+    /// arrays of tables, are written inline as `key = { a = 1, b = 2 }`
+    /// instead of under a `[header]`, as long as they fit in the line width.
+    pub toml_inline_tables: usize,
+    /// Write chains of TOML tables with one key each as a dotted key, like
+    /// `a.b.c = 1`, instead of under a `[a.b]` header.
+    pub toml_dotted_keys: bool,
+}
+
+impl Default for StyleOptions {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            line_width: None,
+            compact: false,
+            trailing_newline: true,
+            key_quotes: KeyQuotes::default(),
+            string_quotes: StringQuotes::default(),
+            yaml: YamlStyle::default(),
+            toml_inline_tables: 0,
+            toml_dotted_keys: false,
+        }
+    }
+}
+
+impl StyleOptions {
+    /// The indentation of one level, falling back to `default` spaces.
+    #[cfg(any(feature = "json", feature = "toml"))]
+    pub(crate) fn indent_or(&self, default: usize) -> String {
+        " ".repeat(self.indent.unwrap_or(default))
+    }
+
+    /// Adds or removes the newline at the end of `out`.
+    pub(crate) fn end(&self, out: &mut String) {
+        match self.trailing_newline {
+            true if !out.ends_with('\n') => out.push('\n'),
+            true => {}
+            false => out.truncate(out.trim_end_matches('\n').len()),
+        }
+    }
+
+    /// Whether text of `width` columns, starting `column` columns in, goes
+    /// on one line.
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+    pub(crate) fn fits(&self, column: usize, width: usize) -> bool {
+        self.compact || self.line_width.is_some_and(|max| column + width <= max)
+    }
+}
+
+/// Which keys get quotes in formats where keys can be bare: TOML, YAML and
+/// JSON5.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyQuotes {
+    /// Only keys that would not read back otherwise.
+    #[default]
+    Minimal,
+    Always,
+}
+
+impl FromStr for KeyQuotes {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "always" => Ok(Self::Always),
+            _ => Err(format!("unknown key quoting '{s}'")),
+        }
+    }
+}
+
+/// How strings are quoted. Strings that single quotes can not hold, like
+/// ones with control characters, always get double quotes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StringQuotes {
+    /// Double quotes, and no quotes at all for YAML strings that read back
+    /// the same without.
+    #[default]
+    Minimal,
+    /// Double quotes on every string.
+    Double,
+    /// Single quotes on every string, as TOML literal strings. JSON and
+    /// JSONC have none and get double quotes.
+    Single,
+}
+
+impl FromStr for StringQuotes {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "double" => Ok(Self::Double),
+            "single" => Ok(Self::Single),
+            _ => Err(format!("unknown string quoting '{s}'")),
+        }
+    }
+}
+
+/// How YAML collections are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YamlStyle {
+    /// Indented `key: value` lines and `- item`s.
+    #[default]
+    Block,
+    /// `{ }` and `[ ]`, like JSON.
+    Flow,
+}
+
+impl FromStr for YamlStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Self::Block),
+            "flow" => Ok(Self::Flow),
+            _ => Err(format!("unknown YAML style '{s}'")),
+        }
+    }
+}
@@ -1,71 +1,518 @@
-use crate::value;
+use crate::comment::{Comment, Comments};
+use crate::detect::Parsed;
+use crate::error::Error;
+use crate::format::Format;
+#[cfg(feature = "json")]
+use crate::json5;
+use crate::options::{LoadOptions, Options};
+use crate::parse;
+use crate::path::{Path, Segment, ToPath};
+use crate::registry;
+use crate::report::{ConversionReport, LossKind, Lossy};
+use crate::style::StyleOptions;
+#[cfg(feature = "toml")]
+use crate::toml_emitter;
+#[cfg(feature = "yaml")]
+use crate::yaml_emitter;
+use indexmap::IndexMap;
 use std::collections::HashMap;
-use thiserror::Error;
-
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("Invalid value: {0}")]
-    ValueError(value::Error),
-    #[error("Failed to convert TOML into table: {0}")]
-    TomlError(toml::ser::Error),
-    #[error("Failed to convert YAML into table: {0}")]
-    YamlError(i32)
-}
 
-use crate::value::Value;
+#[cfg(feature = "yaml")]
+use crate::value::KeyPolicy;
+use crate::value::{IniOptions, Value};
 
+/// Keys keep the order they were inserted in, which for a converted document
+/// is the order of the source.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Table {
-    pub items: HashMap<String, Value>,
+    pub items: IndexMap<String, Value>,
+    /// The original keys of items whose key was not a string in the source,
+    /// by their string form. Only YAML output writes these back.
+    pub typed_keys: HashMap<String, Value>,
+    /// Comments from the source, by the path of the value they belong to
+    /// relative to this table. Each is kept in the innermost table around
+    /// its value, so it moves along when the table does. Only read when
+    /// asked for, see [`Table::attach_comments`].
+    pub comments: Comments,
 }
 
 impl Table {
     pub fn with_capacity(cap: usize) -> Self {
         Self {
-            items: HashMap::with_capacity(cap),
+            items: IndexMap::with_capacity(cap),
+            typed_keys: HashMap::new(),
+            comments: Comments::new(),
         }
     }
-    pub fn new(items: impl Into<HashMap<String, Value>>) -> Self {
+    pub fn new(items: impl Into<IndexMap<String, Value>>) -> Self {
         Self {
             items: items.into(),
+            typed_keys: HashMap::new(),
+            comments: Comments::new(),
+        }
+    }
+    /// Sorts the keys of this table and every table nested in it, for output
+    /// that does not depend on the source order.
+    pub fn sort_keys(&mut self) {
+        self.items.sort_unstable_keys();
+        for val in self.items.values_mut() {
+            val.sort_keys();
+        }
+    }
+
+    /// Stores comments read with [`comment::read`](crate::comment::read) from the source of this
+    /// table. Comments on values that are not in the table are kept, but
+    /// never written.
+    pub fn attach_comments(&mut self, comments: Comments) {
+        for (path, comment) in comments {
+            self.attach_comment(path.segments(), comment);
+        }
+    }
+    fn attach_comment(&mut self, path: &[Segment], comment: Comment) {
+        for depth in (1..path.len()).rev() {
+            if let Some(Value::Table(table)) = value_mut(self, &path[..depth]) {
+                let rest = path[depth..].iter().cloned().collect();
+                table.comments.insert(rest, comment);
+                return;
+            }
         }
+        self.comments
+            .insert(path.iter().cloned().collect(), comment);
+    }
+    /// Every comment in the table and the tables in it, by the full path of
+    /// its value, in document order.
+    pub fn all_comments(&self) -> Vec<(Path, Comment)> {
+        let mut out = vec![];
+        collect_comments(self, &mut Path::new(), &mut out);
+        out
+    }
+
+    /// The value at `path`, like `servers[0].host`. Text that is not a
+    /// valid path finds nothing.
+    pub fn get_path(&self, path: impl ToPath) -> Option<&Value> {
+        let path = path.to_path().ok()?;
+        let (Segment::Key(key), rest) = path.segments().split_first()? else {
+            return None;
+        };
+        let mut val = self.items.get(key)?;
+        for segment in rest {
+            val = match (val, segment) {
+                (Value::Table(t), Segment::Key(key)) => t.items.get(key)?,
+                (Value::Array(a), Segment::Index(i)) => a.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(val)
+    }
+    pub fn get_path_mut(&mut self, path: impl ToPath) -> Option<&mut Value> {
+        value_mut(self, path.to_path().ok()?.segments())
+    }
+    /// Sets the value at `path` and returns the one it replaced. Missing
+    /// tables are made on the way, and arrays for indexes, which may be one
+    /// past the end of an array to append to it.
+    pub fn set_path(
+        &mut self,
+        path: impl ToPath,
+        value: impl Into<Value>,
+    ) -> Result<Option<Value>, Error> {
+        let path = path.to_path()?;
+        let segments = path.segments();
+        let fail = |depth: usize, message: String| {
+            let at: Path = segments[..depth].iter().cloned().collect();
+            let message = match at.is_root() {
+                true => format!("the top level {message}"),
+                false => format!("{at} {message}"),
+            };
+            Error::SetPath {
+                path: path.clone().into_owned(),
+                message,
+            }
+        };
+        match segments.split_first() {
+            Some((Segment::Key(key), rest)) => set_in_table(self, key, rest, value.into())
+                .map_err(|(depth, message)| fail(depth + 1, message)),
+            Some((Segment::Index(_), _)) => Err(fail(0, "is a table, not an array".to_owned())),
+            None => Err(fail(0, "can only be a table".to_owned())),
+        }
+    }
+    /// Removes the value at `path` and returns it. Text that is not a valid
+    /// path removes nothing.
+    pub fn remove_path(&mut self, path: impl ToPath) -> Option<Value> {
+        let path = path.to_path().ok()?;
+        let (last, parent) = path.segments().split_last()?;
+        let table = match parent.is_empty() {
+            true => self,
+            false => match value_mut(self, parent)? {
+                Value::Table(t) => t,
+                Value::Array(a) => match last {
+                    Segment::Index(i) if *i < a.len() => return Some(a.remove(*i)),
+                    _ => return None,
+                },
+                _ => return None,
+            },
+        };
+        let Segment::Key(key) = last else {
+            return None;
+        };
+        table.typed_keys.remove(key);
+        table.items.shift_remove(key)
     }
+
     pub fn from(content: impl Into<Value>) -> Option<Self> {
-        match Value::from(content.into()) {
+        match content.into() {
             Value::Table(t) => Some(t),
             _ => None,
         }
     }
+    /// Parses a single document, with default options.
+    ///
+    /// Parse errors carry the line and column of the problem along with a
+    /// snippet of the source, see [`ParseError`](crate::parse::ParseError).
+    pub fn parse(text: &str, format: Format) -> Result<Self, Error> {
+        Self::parse_with(text, format, &IniOptions::default())
+    }
+    #[cfg_attr(not(feature = "ini"), allow(unused_variables))]
+    pub fn parse_with(text: &str, format: Format, ini: &IniOptions) -> Result<Self, Error> {
+        let not_a_table = Error::NotATable(format);
+        match parse::parse(text, format, &LoadOptions::default())? {
+            #[cfg(feature = "json")]
+            Parsed::Json(json) => Self::from_json(json).ok_or(not_a_table),
+            #[cfg(feature = "toml")]
+            Parsed::Toml(toml) => Self::from_toml(toml).ok_or(not_a_table),
+            #[cfg(feature = "json")]
+            Parsed::Json5(value) => Self::from(value).ok_or(not_a_table),
+            Parsed::Custom(value) => Self::from(value).ok_or(not_a_table),
+            #[cfg(feature = "ini")]
+            Parsed::Ini(content) => Self::from_ini(&content, ini),
+            #[cfg(feature = "json")]
+            Parsed::JsonLines(mut lines) if lines.len() == 1 => {
+                Self::from_json(lines.remove(0)).ok_or(not_a_table)
+            }
+            #[cfg(feature = "json")]
+            Parsed::JsonLines(lines) => Err(Error::DocumentCount(format, lines.len())),
+            #[cfg(feature = "yaml")]
+            Parsed::Yaml(docs) if docs.is_empty() => Ok(Self::default()),
+            #[cfg(feature = "yaml")]
+            Parsed::Yaml(mut docs) if docs.len() == 1 => Self::from_yaml(docs.remove(0)),
+            #[cfg(feature = "yaml")]
+            Parsed::Yaml(docs) => Err(Error::DocumentCount(format, docs.len())),
+        }
+    }
+
+    /// Writes the table as text with default options, laid out as `style`
+    /// asks.
+    pub fn to_string(self, format: Format, style: &StyleOptions) -> Result<String, Error> {
+        self.to_string_with(format, &Options::default(), &IniOptions::default(), style)
+            .map(|(out, _)| out)
+    }
+    /// Writes the table as text. The inverse of [`Table::parse_with`].
+    ///
+    /// JSON Lines is always one line per document and INI has a single
+    /// layout, so `style` only decides their trailing newline.
+    #[cfg_attr(
+        not(all(feature = "json", feature = "toml", feature = "yaml", feature = "ini")),
+        allow(unused_variables)
+    )]
+    pub fn to_string_with(
+        self,
+        format: Format,
+        options: &Options,
+        ini: &IniOptions,
+        style: &StyleOptions,
+    ) -> Result<(String, ConversionReport), Error> {
+        let comments: Comments = self.all_comments().into_iter().collect();
+        let (mut out, report) = match format {
+            #[cfg(feature = "json")]
+            Format::Json | Format::Jsonc | Format::Json5 => {
+                json5::write(&Value::Table(self), format, options, style, &comments)?
+            }
+            #[cfg(feature = "json")]
+            Format::JsonLines => {
+                let (json, report) = self.to_json_with(options)?;
+                (json.to_string(), report)
+            }
+            #[cfg(feature = "toml")]
+            Format::Toml => toml_emitter::emit(&self, options, &comments, style)?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                let (yaml, mut report) = self.to_yaml_with(options)?;
+                let (out, dropped) = yaml_emitter::emit(&yaml, &comments, style);
+                report.entries.extend(dropped.entries);
+                (out, report)
+            }
+            #[cfg(feature = "ini")]
+            Format::Ini => {
+                let (ini, report) = self.to_ini_with(ini)?;
+                let mut out = vec![];
+                ini.write_to(&mut out)
+                    .map_err(|e| Error::serialize(format, e))?;
+                (
+                    String::from_utf8(out).map_err(|e| Error::serialize(format, e))?,
+                    report,
+                )
+            }
+            Format::Custom(name) => {
+                let custom = registry::get(name).ok_or(Error::Unregistered(name))?;
+                let mut report = ConversionReport::default();
+                comments_dropped(self.all_comments(), &mut report);
+                let out = custom
+                    .emit(&Value::Table(self), style)
+                    .map_err(|source| Error::Serialize { format, source })?;
+                (
+                    String::from_utf8(out).map_err(|e| Error::serialize(format, e))?,
+                    report,
+                )
+            }
+        };
+        style.end(&mut out);
+        Ok((out, report))
+    }
+
+    #[cfg(feature = "json")]
     pub fn from_json(content: serde_json::Value) -> Option<Self> {
         match Value::from(content) {
             Value::Table(t) => Some(t),
             _ => None,
         }
     }
-    pub fn to_json(self) -> serde_json::Value {
-        Value::Table(self).into()
+    #[cfg(feature = "json")]
+    pub fn to_json(self) -> Result<serde_json::Value, Error> {
+        Value::Table(self).try_into()
     }
+    #[cfg(feature = "json")]
+    pub fn to_json_with(
+        self,
+        options: &Options,
+    ) -> Result<(serde_json::Value, ConversionReport), Error> {
+        let comments = self.all_comments();
+        let (json, mut report) = Value::Table(self).into_json(options)?;
+        comments_dropped(comments, &mut report);
+        Ok((json, report))
+    }
+    #[cfg(feature = "toml")]
     pub fn from_toml(content: impl Into<Value>) -> Option<Self> {
         Self::from(content)
     }
 
+    #[cfg(feature = "toml")]
     pub fn to_toml(self) -> Result<toml::Table, Error> {
-        Value::Table(self)
-            .try_into()
-            .map_err(|e| Error::ValueError(e))
-            .and_then(|v: toml::Value| toml::Table::try_from(v).map_err(|e| Error::TomlError(e)))
+        self.to_toml_with(&Options::default())
+            .map(|(table, _)| table)
+    }
+    #[cfg(feature = "toml")]
+    pub fn to_toml_with(self, options: &Options) -> Result<(toml::Table, ConversionReport), Error> {
+        match Value::Table(self).into_toml(options)? {
+            (toml::Value::Table(t), report) => Ok((t, report)),
+            _ => unreachable!("tables convert to TOML tables"),
+        }
     }
 
+    #[cfg(feature = "yaml")]
     pub fn from_yaml(content: yaml_rust::Yaml) -> Result<Self, Error> {
-        match Value::try_from(content) {
-            Ok(Value::Table(t)) => Ok(t),
-            Ok(_) => Err(Error::ValueError(value::Error::InvalidValue(
-                "Not a table".to_owned(),
-            ))),
-            Err(e) => Err(Error::ValueError(e)),
+        Self::from_yaml_with(content, KeyPolicy::default())
+    }
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_with(content: yaml_rust::Yaml, keys: KeyPolicy) -> Result<Self, Error> {
+        match Value::from_yaml(content, keys)? {
+            Value::Table(t) => Ok(t),
+            _ => Err(Error::NotATable(Format::Yaml)),
+        }
+    }
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(self) -> Result<yaml_rust::Yaml, Error> {
+        Value::Table(self).try_into()
+    }
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml_with(
+        self,
+        options: &Options,
+    ) -> Result<(yaml_rust::Yaml, ConversionReport), Error> {
+        Value::Table(self).into_yaml(options)
+    }
+
+    #[cfg(feature = "ini")]
+    pub fn from_ini(content: &ini::Ini, options: &IniOptions) -> Result<Self, Error> {
+        match Value::from_ini(content, options)? {
+            Value::Table(t) => Ok(t),
+            _ => unreachable!("INI documents are always tables"),
         }
     }
-    pub fn to_yaml(self) -> yaml_rust::Yaml {
-        Value::Table(self).into()
+    #[cfg(feature = "ini")]
+    pub fn to_ini(self, options: &IniOptions) -> Result<ini::Ini, Error> {
+        self.to_ini_with(options).map(|(ini, _)| ini)
+    }
+    #[cfg(feature = "ini")]
+    pub fn to_ini_with(self, options: &IniOptions) -> Result<(ini::Ini, ConversionReport), Error> {
+        let comments = self.all_comments();
+        let (ini, mut report) = Value::Table(self).into_ini(options)?;
+        comments_dropped(comments, &mut report);
+        Ok((ini, report))
+    }
+}
+
+impl Value {
+    /// Writes the value as a document, like [`Table::to_string_with`].
+    /// Values other than tables can only be written as JSON, JSON Lines,
+    /// JSONC, JSON5 and YAML, whose documents need not be tables.
+    #[cfg_attr(
+        not(any(feature = "json", feature = "yaml")),
+        allow(unused_variables, unreachable_code)
+    )]
+    pub fn to_string_with(
+        self,
+        format: Format,
+        options: &Options,
+        ini: &IniOptions,
+        style: &StyleOptions,
+    ) -> Result<(String, ConversionReport), Error> {
+        let val = match self {
+            Self::Table(table) => return table.to_string_with(format, options, ini, style),
+            val => val,
+        };
+        let (mut out, report) = match format {
+            #[cfg(feature = "json")]
+            Format::Json | Format::Jsonc | Format::Json5 => {
+                json5::write(&val, format, options, style, &Comments::new())?
+            }
+            #[cfg(feature = "json")]
+            Format::JsonLines => {
+                let (json, report) = val.into_json(options)?;
+                (json.to_string(), report)
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                let (yaml, mut report) = val.into_yaml(options)?;
+                let (out, dropped) = yaml_emitter::emit(&yaml, &Comments::new(), style);
+                report.entries.extend(dropped.entries);
+                (out, report)
+            }
+            _ => return Err(Error::NotATable(format)),
+        };
+        style.end(&mut out);
+        Ok((out, report))
+    }
+}
+
+fn value_mut<'a>(table: &'a mut Table, path: &[Segment]) -> Option<&'a mut Value> {
+    let (Segment::Key(key), rest) = path.split_first()? else {
+        return None;
+    };
+    let mut val = table.items.get_mut(key)?;
+    for segment in rest {
+        val = match (val, segment) {
+            (Value::Table(t), Segment::Key(key)) => t.items.get_mut(key)?,
+            (Value::Array(a), Segment::Index(i)) => a.get_mut(*i)?,
+            _ => return None,
+        };
+    }
+    Some(val)
+}
+
+/// Where [`Table::set_path`] went wrong: how far down the path the value
+/// the message is about is, and the message.
+type SetError = (usize, String);
+
+fn set_in_table(
+    table: &mut Table,
+    key: &str,
+    path: &[Segment],
+    value: Value,
+) -> Result<Option<Value>, SetError> {
+    match table.items.get_mut(key) {
+        Some(val) => set_value(val, path, value),
+        None => {
+            let val = build(path, value)?;
+            table.items.insert(key.to_owned(), val);
+            Ok(None)
+        }
     }
 }
+
+/// Sets `value` at `path` below `val`, and returns the value it replaced.
+fn set_value(val: &mut Value, path: &[Segment], value: Value) -> Result<Option<Value>, SetError> {
+    let Some((segment, rest)) = path.split_first() else {
+        return Ok(Some(std::mem::replace(val, value)));
+    };
+    let deeper = |(depth, message): SetError| (depth + 1, message);
+    match (val, segment) {
+        (Value::Table(t), Segment::Key(key)) => set_in_table(t, key, rest, value).map_err(deeper),
+        (Value::Array(a), Segment::Index(i)) => {
+            let len = a.len();
+            match a.get_mut(*i) {
+                Some(val) => set_value(val, rest, value).map_err(deeper),
+                None if *i == len => {
+                    a.push(build(rest, value).map_err(deeper)?);
+                    Ok(None)
+                }
+                None => Err((0, format!("has {len} elements, so no index {i}"))),
+            }
+        }
+        (val, Segment::Key(_)) => Err((0, format!("is {}, not a table", val.kind()))),
+        (val, Segment::Index(_)) => Err((0, format!("is {}, not an array", val.kind()))),
+    }
+}
+
+/// The tables and arrays that hold `value` at `path`, to put where there is
+/// nothing yet. Built whole before they are put in, so that a failed
+/// [`Table::set_path`] changes nothing.
+fn build(path: &[Segment], value: Value) -> Result<Value, SetError> {
+    let Some((segment, rest)) = path.split_first() else {
+        return Ok(value);
+    };
+    let val = build(rest, value).map_err(|(depth, message)| (depth + 1, message))?;
+    match segment {
+        Segment::Key(key) => Ok(Value::Table(Table::new([(key.clone(), val)]))),
+        Segment::Index(0) => Ok(Value::Array(vec![val])),
+        Segment::Index(i) => Err((
+            0,
+            format!("is missing, and a new array starts at index 0, not {i}"),
+        )),
+    }
+}
+
+fn collect_comments(table: &Table, path: &mut Path, out: &mut Vec<(Path, Comment)>) {
+    for (key, val) in &table.items {
+        let mut relative = Path::new();
+        relative.push(Segment::Key(key.clone()));
+        path.push(Segment::Key(key.clone()));
+        collect_value_comments(table, val, &mut relative, path, out);
+        path.pop();
+    }
+}
+
+/// Collects the comments of `val`, found at `relative` in the comments of
+/// `table`, and of everything in it.
+fn collect_value_comments(
+    table: &Table,
+    val: &Value,
+    relative: &mut Path,
+    path: &mut Path,
+    out: &mut Vec<(Path, Comment)>,
+) {
+    if let Some(comment) = table.comments.get(relative) {
+        out.push((path.clone(), comment.clone()));
+    }
+    match val {
+        Value::Table(t) => collect_comments(t, path, out),
+        Value::Array(a) => {
+            for (i, val) in a.iter().enumerate() {
+                relative.push(Segment::Index(i));
+                path.push(Segment::Index(i));
+                collect_value_comments(table, val, relative, path, out);
+                path.pop();
+                relative.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reports comments lost in output that has no comments.
+fn comments_dropped(comments: Vec<(Path, Comment)>, report: &mut ConversionReport) {
+    report
+        .entries
+        .extend(comments.into_iter().map(|(path, _)| Lossy {
+            path,
+            kind: LossKind::Dropped("comment"),
+        }));
+}
@@ -0,0 +1,531 @@
+use std::fmt::Write;
+
+use crate::comment::{Comment, Comments};
+use crate::error::Error;
+use crate::options::Options;
+use crate::path::Segment;
+use crate::report::{ConversionReport, Ctx, LossKind};
+use crate::style::{KeyQuotes, StringQuotes, StyleOptions};
+use crate::table::Table;
+use crate::value::{toml_fitted, Value};
+
+/// Writes `table` as TOML, with `comments` above and after the values they
+/// belong to. Nulls and integers TOML can not hold follow `options`, as in
+/// [`Table::to_toml_with`].
+///
+/// Values come before tables, so none of them ends up in a table that
+/// follows; values moved ahead of tables are listed in the report. Tables get a `[header]` of their own, or are written inline or
+/// as dotted keys when the style asks for it. Arrays made only of tables
+/// become `[[arrays.of.tables]]`; tables in other arrays can only be inline.
+/// Comments in places TOML has no room for, like inside inline tables, are
+/// left out and listed in the report.
+pub fn emit(
+    table: &Table,
+    options: &Options,
+    comments: &Comments,
+    style: &StyleOptions,
+) -> Result<(String, ConversionReport), Error> {
+    let mut emitter = Emitter {
+        out: String::new(),
+        comments,
+        style,
+        indent: style.indent_or(4),
+        flat: false,
+        ctx: Ctx::new(options),
+    };
+    emitter.section(table)?;
+    Ok((emitter.out, emitter.ctx.report))
+}
+
+struct Emitter<'a> {
+    out: String,
+    comments: &'a Comments,
+    style: &'a StyleOptions,
+    indent: String,
+    /// Whether the value being written goes on one line.
+    flat: bool,
+    ctx: Ctx<'a>,
+}
+
+fn is_array_of_tables(array: &[Value]) -> bool {
+    !array.is_empty() && array.iter().all(|val| matches!(val, Value::Table(_)))
+}
+
+/// A value written after a key, as opposed to under a header.
+enum Entry<'a> {
+    Value(&'a Value),
+    /// A chain of tables with one key each, like `a.b.c = 1`. Holds the keys
+    /// after the first and the value at the end.
+    Dotted(Vec<&'a str>, &'a Value),
+}
+
+// Writing to a `String` never fails.
+impl Emitter<'_> {
+    fn comment(&self) -> Option<&Comment> {
+        self.comments.get(&self.ctx.path)
+    }
+
+    /// Whether anything inside the current value has a comment.
+    fn comments_inside(&self) -> bool {
+        let depth = self.ctx.path.segments().len();
+        self.comments
+            .keys()
+            .any(|path| path.segments().len() > depth && path.starts_with(&self.ctx.path))
+    }
+
+    fn column(&self) -> usize {
+        let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line..].chars().count()
+    }
+
+    fn write_indent(&mut self, level: usize) {
+        for _ in 0..level {
+            self.out.push_str(&self.indent);
+        }
+    }
+
+    /// Writes the comments above the current value, or notes them as left
+    /// out if it is on one line with others.
+    fn write_before(&mut self, level: usize) {
+        let comments = self.comments;
+        let Some(comment) = comments.get(&self.ctx.path) else {
+            return;
+        };
+        if self.flat {
+            self.ctx.lossy(LossKind::Dropped("comment"));
+            return;
+        }
+        for line in &comment.before {
+            self.write_indent(level);
+            match line.is_empty() {
+                true => self.out.push('#'),
+                false => write!(self.out, "# {line}").unwrap(),
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn write_after(&mut self) {
+        if self.flat {
+            return;
+        }
+        if let Some(after) = self.comment().and_then(|c| c.after.clone()) {
+            match after.is_empty() {
+                true => self.out.push_str(" #"),
+                false => write!(self.out, " # {after}").unwrap(),
+            }
+        }
+    }
+
+    /// Notes a key that was not a string in the source.
+    fn check_key(&mut self, table: &Table, key: &str) {
+        if table.typed_keys.contains_key(key) {
+            self.ctx.lossy(LossKind::KeyAsString);
+        }
+    }
+
+    /// How `val`, at `key` in the current table, is written after its key,
+    /// or `None` if it goes under a header of its own.
+    fn entry<'v>(&mut self, key: &str, val: &'v Value) -> Result<Option<Entry<'v>>, Error> {
+        Ok(match val {
+            Value::Table(t) => {
+                if let Some((keys, end)) = self.dotted(t) {
+                    Some(Entry::Dotted(keys, end))
+                } else if self.fits_inline(key, t)? {
+                    Some(Entry::Value(val))
+                } else {
+                    None
+                }
+            }
+            Value::Array(a) if is_array_of_tables(a) => None,
+            val => Some(Entry::Value(val)),
+        })
+    }
+
+    /// The keys and the value at the end of a chain of tables with one key
+    /// each, if the style collapses them into a dotted key. Chains that end
+    /// in a table or an array of tables keep their headers, and ones that
+    /// end in a null keep the tables that would be all that is left of it.
+    fn dotted<'v>(&self, table: &'v Table) -> Option<(Vec<&'v str>, &'v Value)> {
+        if !self.style.toml_dotted_keys {
+            return None;
+        }
+        let mut keys = vec![];
+        let mut table = table;
+        loop {
+            if table.items.len() != 1 {
+                return None;
+            }
+            let (key, val) = table.items.first()?;
+            keys.push(key.as_str());
+            match val {
+                Value::Table(t) => table = t,
+                Value::Array(a) if is_array_of_tables(a) => return None,
+                Value::Null => return None,
+                val => return Some((keys, val)),
+            }
+        }
+    }
+
+    /// Whether the table `key`, in the current table, is small enough to be
+    /// written inline, see [`StyleOptions::toml_inline_tables`].
+    fn fits_inline(&mut self, key: &str, table: &Table) -> Result<bool, Error> {
+        let small = table.items.len() <= self.style.toml_inline_tables
+            && table.items.values().all(|val| match val {
+                Value::Table(_) => false,
+                Value::Array(a) => !is_array_of_tables(a),
+                _ => true,
+            });
+        if self.style.toml_inline_tables == 0 || !small {
+            return Ok(false);
+        }
+        self.ctx.path.push(Segment::Key(key.to_owned()));
+        let fits = !self.comments_inside() && {
+            let (start, reported) = (self.out.len(), self.ctx.report.entries.len());
+            self.flat = true;
+            let written = self.inline_table(table);
+            self.flat = false;
+            let width = key_text(key, self.style).len() + 3 + self.out[start..].chars().count();
+            self.out.truncate(start);
+            self.ctx.report.entries.truncate(reported);
+            written?;
+            self.style.compact || self.style.line_width.is_none_or(|max| width <= max)
+        };
+        self.ctx.path.pop();
+        Ok(fits)
+    }
+
+    /// Writes the values of the table at the current path, then the tables
+    /// in it under headers of their own.
+    fn section(&mut self, table: &Table) -> Result<(), Error> {
+        let mut sections = vec![];
+        for (key, val) in &table.items {
+            match self.entry(key, val)? {
+                Some(entry) => {
+                    self.ctx.path.push(Segment::Key(key.clone()));
+                    self.check_key(table, key);
+                    let len = self.out.len();
+                    let written = self.key_value(key, entry);
+                    if !sections.is_empty() && self.out.len() > len {
+                        self.ctx.lossy(LossKind::MovedBeforeTables);
+                    }
+                    self.ctx.path.pop();
+                    written?;
+                }
+                None => sections.push((key, val)),
+            }
+        }
+        for (key, val) in sections {
+            self.ctx.path.push(Segment::Key(key.clone()));
+            self.check_key(table, key);
+            let written = match val {
+                Value::Table(t) => self.table_section(t),
+                Value::Array(a) => self.array_of_tables(a),
+                _ => unreachable!("only tables and arrays of tables are sections"),
+            };
+            self.ctx.path.pop();
+            written?;
+        }
+        Ok(())
+    }
+
+    /// Writes `key = value` on a line of its own, or nothing at all for a
+    /// null that is left out.
+    fn key_value(&mut self, key: &str, entry: Entry) -> Result<(), Error> {
+        let (keys, val) = match entry {
+            Entry::Value(val) => (vec![], val),
+            Entry::Dotted(keys, val) => (keys, val),
+        };
+        let mut names = vec![key_text(key, self.style)];
+        for key in &keys {
+            self.write_before(0);
+            self.ctx.path.push(Segment::Key(key.to_string()));
+            names.push(key_text(key, self.style));
+        }
+        let written = self.dotted_value(names, val);
+        for _ in &keys {
+            self.ctx.path.pop();
+        }
+        written
+    }
+
+    /// Writes the value at the end of the keys in `names`, which the current
+    /// path leads to.
+    fn dotted_value(&mut self, names: Vec<String>, val: &Value) -> Result<(), Error> {
+        let Some(val) = toml_fitted(val, &mut self.ctx, false)? else {
+            return Ok(());
+        };
+        self.write_before(0);
+        self.out.push_str(&names.join("."));
+        self.out.push_str(" = ");
+        self.value(&val, 0)?;
+        self.write_after();
+        self.out.push('\n');
+        Ok(())
+    }
+
+    /// Writes a table with a header. Tables that only hold other tables
+    /// leave theirs out, unless it has comments to carry.
+    fn table_section(&mut self, table: &Table) -> Result<(), Error> {
+        let mut own_entries = table.items.is_empty() || self.comment().is_some();
+        for (key, val) in &table.items {
+            own_entries = own_entries || self.entry(key, val)?.is_some();
+        }
+        if own_entries {
+            self.blank_line();
+            self.header(false);
+        }
+        self.section(table)
+    }
+
+    fn array_of_tables(&mut self, array: &[Value]) -> Result<(), Error> {
+        for (i, val) in array.iter().enumerate() {
+            let Value::Table(table) = val else {
+                unreachable!("arrays of tables only hold tables");
+            };
+            self.blank_line();
+            // The comments of the whole array go above the first of its
+            // tables.
+            if i == 0 {
+                self.write_before(0);
+            }
+            self.ctx.path.push(Segment::Index(i));
+            self.header(true);
+            let written = self.section(table);
+            self.ctx.path.pop();
+            written?;
+        }
+        Ok(())
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.style.compact {
+            self.out.push('\n');
+        }
+    }
+
+    fn header(&mut self, array: bool) {
+        self.write_before(0);
+        let keys: Vec<String> = self
+            .ctx
+            .path
+            .segments()
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Key(key) => Some(key_text(key, self.style)),
+                Segment::Index(_) => None,
+            })
+            .collect();
+        let (open, close) = if array { ("[[", "]]") } else { ("[", "]") };
+        write!(self.out, "{open}{}{close}", keys.join(".")).unwrap();
+        self.write_after();
+        self.out.push('\n');
+    }
+
+    /// Writes a value after its key or in an array nested `level` deep.
+    /// Nulls and integers that do not fit were already dealt with.
+    fn value(&mut self, val: &Value, level: usize) -> Result<(), Error> {
+        match val {
+            Value::String(s) => write_string(
+                &mut self.out,
+                s,
+                self.style.string_quotes,
+                level == 0 && !self.flat && !self.style.compact,
+            ),
+            Value::Int(i) => write!(self.out, "{i}").unwrap(),
+            Value::UInt(i) => write!(self.out, "{i}").unwrap(),
+            Value::Float(f) => write_float(&mut self.out, *f),
+            Value::Bool(b) => write!(self.out, "{b}").unwrap(),
+            Value::Datetime(dt) => write!(self.out, "{dt}").unwrap(),
+            Value::Array(a) => self.array(a, level)?,
+            Value::Table(t) => self.inline_table(t)?,
+            Value::Null => unreachable!("nulls are dropped or replaced first"),
+        }
+        Ok(())
+    }
+
+    /// Writes an array element, unless it is a null that is left out.
+    fn element(&mut self, val: &Value, level: usize, first: bool) -> Result<bool, Error> {
+        let Some(val) = toml_fitted(val, &mut self.ctx, true)? else {
+            return Ok(false);
+        };
+        match self.flat {
+            true => {
+                if !first {
+                    self.out.push_str(", ");
+                }
+                self.write_before(0);
+                self.value(&val, level + 1)?;
+            }
+            false => {
+                self.out.push('\n');
+                self.write_before(level + 1);
+                self.write_indent(level + 1);
+                self.value(&val, level + 1)?;
+                self.out.push(',');
+                self.write_after();
+            }
+        }
+        Ok(true)
+    }
+
+    fn elements(&mut self, array: &[Value], level: usize) -> Result<(), Error> {
+        let mut first = true;
+        for (i, val) in array.iter().enumerate() {
+            self.ctx.path.push(Segment::Index(i));
+            let written = self.element(val, level, first);
+            self.ctx.path.pop();
+            first &= !written?;
+        }
+        Ok(())
+    }
+
+    /// Writes an array on one line if it fits, or with an element per line
+    /// otherwise.
+    fn array(&mut self, array: &[Value], level: usize) -> Result<(), Error> {
+        let try_flat = !self.flat
+            && (self.style.compact || self.style.line_width.is_some() && !self.comments_inside());
+        if self.flat || try_flat {
+            let (start, column) = (self.out.len(), self.column());
+            let reported = self.ctx.report.entries.len();
+            let was_flat = std::mem::replace(&mut self.flat, true);
+            self.out.push('[');
+            let written = self.elements(array, level);
+            self.out.push(']');
+            self.flat = was_flat;
+            written?;
+            let width = self.out[start..].chars().count();
+            if self.flat || self.style.fits(column, width) {
+                return Ok(());
+            }
+            self.out.truncate(start);
+            self.ctx.report.entries.truncate(reported);
+        }
+        let start = self.out.len();
+        self.out.push('[');
+        self.elements(array, level)?;
+        // Every element may have been a null that was left out.
+        if self.out.len() == start + 1 {
+            self.out.push(']');
+            return Ok(());
+        }
+        self.out.push('\n');
+        self.write_indent(level);
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// Writes a table as `{ key = value }`, which TOML keeps on one line.
+    fn inline_table(&mut self, table: &Table) -> Result<(), Error> {
+        let was_flat = std::mem::replace(&mut self.flat, true);
+        let start = self.out.len();
+        self.out.push('{');
+        for (key, val) in &table.items {
+            self.ctx.path.push(Segment::Key(key.clone()));
+            self.check_key(table, key);
+            let written = match toml_fitted(val, &mut self.ctx, false) {
+                Ok(Some(val)) => {
+                    self.out.push_str(if self.out.len() == start + 1 {
+                        " "
+                    } else {
+                        ", "
+                    });
+                    self.write_before(0);
+                    self.out.push_str(&key_text(key, self.style));
+                    self.out.push_str(" = ");
+                    self.value(&val, 1)
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            self.ctx.path.pop();
+            written?;
+        }
+        self.out.push_str(if self.out.len() == start + 1 {
+            "}"
+        } else {
+            " }"
+        });
+        self.flat = was_flat;
+        Ok(())
+    }
+}
+
+/// Keys made of ASCII letters, digits, `_` and `-` can be bare.
+fn key_text(key: &str, style: &StyleOptions) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare && style.key_quotes == KeyQuotes::Minimal {
+        return key.to_owned();
+    }
+    let mut out = String::new();
+    let quotes = match style.string_quotes {
+        StringQuotes::Single => StringQuotes::Single,
+        _ => StringQuotes::Double,
+    };
+    write_string(&mut out, key, quotes, false);
+    out
+}
+
+/// Writes a string, as a literal string where that saves escapes or
+/// `quotes` asks for one, and over several lines if it has line breaks and
+/// `multi_line` allows.
+fn write_string(out: &mut String, s: &str, quotes: StringQuotes, multi_line: bool) {
+    let multi_line = multi_line && s.contains('\n');
+    let controls = s.contains(|c: char| c.is_control() && c != '\t' && (c != '\n' || !multi_line));
+    let literal_ok = !controls
+        && match multi_line {
+            true => !s.contains("'''") && !s.ends_with('\''),
+            false => !s.contains('\''),
+        };
+    let literal = literal_ok
+        && match quotes {
+            StringQuotes::Minimal => s.contains(['"', '\\']),
+            StringQuotes::Double => false,
+            StringQuotes::Single => true,
+        };
+    match (literal, multi_line) {
+        (true, false) => write!(out, "'{s}'").unwrap(),
+        (true, true) => write!(out, "'''\n{s}'''").unwrap(),
+        (false, false) => {
+            out.push('"');
+            escape(out, s, false);
+            out.push('"');
+        }
+        (false, true) => {
+            out.push_str("\"\"\"\n");
+            escape(out, s, true);
+            out.push_str("\"\"\"");
+        }
+    }
+}
+
+/// Escapes a basic string, leaving line breaks in multi-line ones.
+fn escape(out: &mut String, s: &str, multi_line: bool) {
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' if multi_line => out.push('\n'),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\x08' => out.push_str("\\b"),
+            '\x0c' => out.push_str("\\f"),
+            c if c.is_control() => write!(out, "\\u{:04X}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Floats keep a `.` or exponent, so they are not read back as integers.
+fn write_float(out: &mut String, f: f64) {
+    match f {
+        f if f.is_nan() => out.push_str("nan"),
+        f if f.is_infinite() && f > 0.0 => out.push_str("inf"),
+        f if f.is_infinite() => out.push_str("-inf"),
+        f => write!(out, "{f:?}").unwrap(),
+    }
+}
@@ -1,17 +1,39 @@
-use std::collections::HashMap;
-use thiserror::Error;
+#[cfg(feature = "toml")]
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(feature = "json")]
+use indexmap::IndexMap;
+#[cfg(feature = "yaml")]
 use yaml_rust::yaml;
 
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::error::{Error, Unsupported};
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::format::Format;
+#[cfg(feature = "yaml")]
+use crate::options::DatetimeStyle;
+#[cfg(feature = "json")]
+use crate::options::NonFinitePolicy;
+#[cfg(feature = "toml")]
+use crate::options::NullPolicy;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::options::Options;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use crate::options::OverflowPolicy;
+#[cfg(any(feature = "yaml", feature = "ini"))]
+use crate::path::Path;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::path::Segment;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::report::ConversionReport;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "ini"))]
+use crate::report::{Ctx, LossKind};
 use crate::table::Table;
+#[cfg(feature = "json")]
 use serde_json as sj;
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("Unsupported type: '{0}'")]
-    UnsupportedType(&'static str),
-    #[error("Invalid value: {0}")]
-    InvalidValue(String),
-}
+pub use toml_datetime::Datetime;
 
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum Value {
@@ -22,26 +44,128 @@ pub enum Value {
     Float(f64),
     String(String),
     Bool(bool),
+    /// An offset date-time, local date-time, local date or local time.
+    Datetime(Datetime),
     Array(Vec<Value>),
     Table(Table),
 }
 
+impl Value {
+    /// Sorts the keys of every table in this value, see [`Table::sort_keys`].
+    pub fn sort_keys(&mut self) {
+        match self {
+            Self::Table(t) => t.sort_keys(),
+            Self::Array(a) => a.iter_mut().for_each(Self::sort_keys),
+            _ => {}
+        }
+    }
+
+    /// What kind of value this is, for messages, like `a table`.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Int(_) | Self::UInt(_) => "an integer",
+            Self::Float(_) => "a float",
+            Self::String(_) => "a string",
+            Self::Bool(_) => "a boolean",
+            Self::Datetime(_) => "a datetime",
+            Self::Array(_) => "an array",
+            Self::Table(_) => "a table",
+        }
+    }
+}
+
+/// A compact, JSON-like rendering for messages, like `{"port": 80}`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::UInt(i) => write!(f, "{i}"),
+            Self::Float(x) => write!(f, "{x:?}"),
+            Self::String(s) => write!(f, "{s:?}"),
+            Self::Datetime(dt) => write!(f, "{dt}"),
+            Self::Array(a) => {
+                f.write_str("[")?;
+                for (i, val) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{val}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Table(t) => {
+                f.write_str("{")?;
+                for (i, (key, val)) in t.items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key:?}: {val}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<Table> for Value {
+    fn from(table: Table) -> Self {
+        Self::Table(table)
+    }
+}
+
 /***********************************************/
 // JSON
 
-impl Into<serde_json::Value> for Value {
-    fn into(self) -> serde_json::Value {
+#[cfg(feature = "json")]
+impl Value {
+    pub fn into_json(
+        self,
+        options: &Options,
+    ) -> Result<(serde_json::Value, ConversionReport), Error> {
+        let mut ctx = Ctx::new(options);
+        let value = self.json_value(&mut ctx)?;
+        Ok((value, ctx.report))
+    }
+
+    fn json_value(self, ctx: &mut Ctx) -> Result<serde_json::Value, Error> {
         use serde_json::Value as JVal;
-        match self {
+        Ok(match self {
             Self::Null => JVal::Null,
             Self::Bool(b) => JVal::Bool(b),
-            Self::Float(f) => JVal::Number(sj::Number::from_f64(f).unwrap()),
+            Self::Float(f) => match sj::Number::from_f64(f) {
+                Some(n) => JVal::Number(n),
+                None => match ctx.options.non_finite {
+                    NonFinitePolicy::Error => {
+                        return Err(Error::unsupported(
+                            Format::Json,
+                            &ctx.path,
+                            Unsupported::NonFiniteFloat(f),
+                        ))
+                    }
+                    NonFinitePolicy::Null => {
+                        ctx.lossy(LossKind::NonFiniteAsNull(f));
+                        JVal::Null
+                    }
+                    NonFinitePolicy::String => {
+                        ctx.lossy(LossKind::NonFiniteAsString(f));
+                        JVal::String(non_finite_name(f).to_owned())
+                    }
+                },
+            },
             Self::Int(i) => JVal::Number(sj::Number::from(i)),
             Self::UInt(i) => JVal::Number(sj::Number::from(i)),
+            // JSON has no datetime type, whatever the `DatetimeStyle`.
+            Self::Datetime(dt) => {
+                ctx.lossy(LossKind::DatetimeAsString);
+                JVal::String(dt.to_string())
+            }
             Self::Array(a) => JVal::Array({
-                let mut new_array = vec![];
-                for val in a {
-                    new_array.push(val.into());
+                let mut new_array = Vec::with_capacity(a.len());
+                for (i, val) in a.into_iter().enumerate() {
+                    new_array.push(ctx.at(Segment::Index(i), |ctx| val.json_value(ctx))?);
                 }
                 new_array
             }),
@@ -49,15 +173,42 @@ impl Into<serde_json::Value> for Value {
             Self::Table(t) => JVal::Object({
                 let mut items = serde_json::Map::with_capacity(t.items.len());
                 for (name, val) in t.items {
-                    items.insert(name, val.into());
+                    let val = ctx.at(Segment::Key(name.clone()), |ctx| {
+                        if t.typed_keys.contains_key(&name) {
+                            ctx.lossy(LossKind::KeyAsString);
+                        }
+                        val.json_value(ctx)
+                    })?;
+                    items.insert(name, val);
                 }
 
                 items
             }),
-        }
+        })
     }
 }
 
+/// The JavaScript spelling of a non-finite float, also used by JSON5.
+#[cfg(feature = "json")]
+pub(crate) fn non_finite_name(f: f64) -> &'static str {
+    if f.is_nan() {
+        "NaN"
+    } else if f > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.into_json(&Options::default()).map(|(value, _)| value)
+    }
+}
+
+#[cfg(feature = "json")]
 impl From<serde_json::Value> for Value {
     fn from(value: sj::Value) -> Self {
         use serde_json::Value as JVal;
@@ -76,7 +227,7 @@ impl From<serde_json::Value> for Value {
             JVal::String(s) => Self::String(s),
             JVal::Array(a) => Self::Array(a.into_iter().map(|v| v.into()).collect()),
             JVal::Object(o) => Self::Table(Table::new({
-                let mut items = HashMap::with_capacity(o.len());
+                let mut items = IndexMap::with_capacity(o.len());
                 for (name, val) in o {
                     items.insert(name, Self::from(val));
                 }
@@ -89,18 +240,20 @@ impl From<serde_json::Value> for Value {
 /***********************************************/
 // Toml
 
+#[cfg(feature = "toml")]
 impl From<toml::Table> for Value {
     fn from(value: toml::Table) -> Self {
         Self::from(toml::Value::Table(value))
     }
 }
 
+#[cfg(feature = "toml")]
 impl From<toml::Value> for Value {
     fn from(value: toml::Value) -> Self {
         use toml::Value as TVal;
         match value {
             TVal::Boolean(b) => Self::Bool(b),
-            TVal::Datetime(dt) => Self::String(dt.to_string()),
+            TVal::Datetime(dt) => Self::Datetime(dt),
             TVal::Float(f) => Self::Float(f),
             TVal::Integer(i) => {
                 if i >= 0 {
@@ -122,93 +275,655 @@ impl From<toml::Value> for Value {
     }
 }
 
-impl TryInto<toml::Value> for Value {
-    type Error = Error;
-    fn try_into(self) -> Result<toml::Value, Self::Error> {
+/// An unsigned integer made to fit the `i64` TOML and YAML are limited to.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+enum FittedUInt {
+    Int(i64),
+    String(String),
+    Float(f64),
+}
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+impl FittedUInt {
+    fn new(i: u64, format: Format, ctx: &mut Ctx) -> Result<Self, Error> {
+        if let Ok(i) = i64::try_from(i) {
+            return Ok(Self::Int(i));
+        }
+        match ctx.options.uint_overflow {
+            OverflowPolicy::Error => Err(Error::unsupported(
+                format,
+                &ctx.path,
+                Unsupported::IntegerOverflow(i),
+            )),
+            OverflowPolicy::String => {
+                ctx.lossy(LossKind::UIntAsString(i));
+                Ok(Self::String(i.to_string()))
+            }
+            OverflowPolicy::Float => {
+                ctx.lossy(LossKind::UIntAsFloat(i));
+                Ok(Self::Float(i as f64))
+            }
+        }
+    }
+}
+
+/// What becomes of a null in TOML: `None` leaves it out.
+#[cfg(feature = "toml")]
+fn toml_null(ctx: &mut Ctx, in_array: bool) -> Result<Option<toml::Value>, Error> {
+    match &ctx.options.null {
+        NullPolicy::Drop => {}
+        NullPolicy::DropKeys if !in_array && !ctx.path.is_root() => {}
+        NullPolicy::Replace(sentinel) => {
+            let sentinel = sentinel.clone();
+            ctx.lossy(LossKind::NullReplaced(sentinel.clone()));
+            return Ok(Some(toml::Value::String(sentinel)));
+        }
+        NullPolicy::Error | NullPolicy::DropKeys => {
+            return Err(Error::unsupported(
+                Format::Toml,
+                &ctx.path,
+                Unsupported::Null,
+            ))
+        }
+    }
+    if ctx.path.is_root() {
+        return Err(Error::unsupported(
+            Format::Toml,
+            &ctx.path,
+            Unsupported::Null,
+        ));
+    }
+    ctx.lossy(LossKind::NullDropped);
+    Ok(None)
+}
+
+/// What `val` becomes in TOML, as in [`Value::into_toml`]: nulls follow the
+/// [`NullPolicy`] and unsigned integers the [`OverflowPolicy`]. `None` leaves
+/// it out.
+#[cfg(feature = "toml")]
+pub(crate) fn toml_fitted<'a>(
+    val: &'a Value,
+    ctx: &mut Ctx,
+    in_array: bool,
+) -> Result<Option<Cow<'a, Value>>, Error> {
+    Ok(match val {
+        Value::Null => toml_null(ctx, in_array)?.map(|val| Cow::Owned(Value::from(val))),
+        Value::UInt(i) => Some(Cow::Owned(match FittedUInt::new(*i, Format::Toml, ctx)? {
+            FittedUInt::Int(i) => Value::Int(i),
+            FittedUInt::String(s) => Value::String(s),
+            FittedUInt::Float(f) => Value::Float(f),
+        })),
+        val => Some(Cow::Borrowed(val)),
+    })
+}
+
+#[cfg(feature = "toml")]
+impl Value {
+    pub fn into_toml(self, options: &Options) -> Result<(toml::Value, ConversionReport), Error> {
+        let mut ctx = Ctx::new(options);
+        let value = self.toml_value(&mut ctx)?;
+        Ok((value, ctx.report))
+    }
+
+    fn toml_value(self, ctx: &mut Ctx) -> Result<toml::Value, Error> {
         use toml::Value as TVal;
         Ok(match self {
-            Self::Array(a) => TVal::Array(
-                a.into_iter()
-                    .map(TryInto::<TVal>::try_into)
-                    .collect::<Result<Vec<TVal>, Self::Error>>()?,
-            ),
+            Self::Array(a) => TVal::Array({
+                let mut array = Vec::with_capacity(a.len());
+                for (i, val) in a.into_iter().enumerate() {
+                    let val = ctx.at(Segment::Index(i), |ctx| match val {
+                        Self::Null => toml_null(ctx, true),
+                        val => val.toml_value(ctx).map(Some),
+                    })?;
+                    array.extend(val);
+                }
+                array
+            }),
             Self::Bool(b) => TVal::Boolean(b),
             Self::Float(f) => TVal::Float(f),
             Self::Int(i) => TVal::Integer(i),
-            Self::UInt(i) => TVal::Integer(i as i64),
+            Self::UInt(i) => match FittedUInt::new(i, Format::Toml, ctx)? {
+                FittedUInt::Int(i) => TVal::Integer(i),
+                FittedUInt::String(s) => TVal::String(s),
+                FittedUInt::Float(f) => TVal::Float(f),
+            },
+            Self::Datetime(dt) => TVal::Datetime(dt),
             Self::String(s) => TVal::String(s),
             Self::Table(t) => TVal::Table({
                 let mut table = toml::Table::with_capacity(t.items.capacity());
                 for (name, val) in t.items {
-                    table.insert(name, val.try_into()?);
+                    let val = ctx.at(Segment::Key(name.clone()), |ctx| {
+                        if t.typed_keys.contains_key(&name) {
+                            ctx.lossy(LossKind::KeyAsString);
+                        }
+                        match val {
+                            Self::Null => toml_null(ctx, false),
+                            val => val.toml_value(ctx).map(Some),
+                        }
+                    })?;
+                    if let Some(val) = val {
+                        table.insert(name, val);
+                    }
                 }
                 table
             }),
-            Self::Null => return Err(Error::UnsupportedType("null")),
+            Self::Null => match toml_null(ctx, false)? {
+                Some(val) => val,
+                None => unreachable!("a null at the root is never dropped"),
+            },
         })
     }
 }
 
+#[cfg(feature = "toml")]
+impl TryFrom<Value> for toml::Value {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.into_toml(&Options::default()).map(|(value, _)| value)
+    }
+}
 
 /***********************************************/
 // YAML
 
-impl Into<yaml::Yaml> for Value {
-    fn into(self) -> yaml::Yaml {
+#[cfg(feature = "yaml")]
+impl Value {
+    pub fn into_yaml(self, options: &Options) -> Result<(yaml::Yaml, ConversionReport), Error> {
+        let mut ctx = Ctx::new(options);
+        let value = self.yaml_value(&mut ctx)?;
+        Ok((value, ctx.report))
+    }
+
+    fn yaml_value(self, ctx: &mut Ctx) -> Result<yaml::Yaml, Error> {
         use yaml::Yaml;
         use yaml_rust::yaml::Hash;
-        match self {
+        Ok(match self {
             Self::Null => Yaml::Null,
             Self::Bool(b) => Yaml::Boolean(b),
-            Self::Float(f) => Yaml::Real(f.to_string()),
+            Self::Float(f) if f.is_nan() => Yaml::Real(".nan".to_owned()),
+            Self::Float(f) if f.is_infinite() => {
+                Yaml::Real(if f > 0.0 { ".inf" } else { "-.inf" }.to_owned())
+            }
+            // `Debug` always keeps a `.` or exponent, so integral floats are
+            // not read back as integers.
+            Self::Float(f) => Yaml::Real(format!("{f:?}")),
             Self::Int(i) => Yaml::Integer(i),
-            Self::UInt(i) => Yaml::Integer(i as i64),
+            Self::UInt(i) => match FittedUInt::new(i, Format::Yaml, ctx)? {
+                FittedUInt::Int(i) => Yaml::Integer(i),
+                FittedUInt::String(s) => Yaml::String(s),
+                FittedUInt::Float(f) => Yaml::Real(format!("{f:?}")),
+            },
             Self::String(s) => Yaml::String(s),
-            Self::Array(a) => Yaml::Array(a.into_iter().map(Into::into).collect()),
+            // The emitter writes reals verbatim and quotes strings that look
+            // like timestamps, see `yaml_emitter`.
+            Self::Datetime(dt) => match ctx.options.datetime {
+                // YAML timestamps always have a date.
+                DatetimeStyle::Native if dt.date.is_some() => Yaml::Real(dt.to_string()),
+                DatetimeStyle::Native | DatetimeStyle::String => {
+                    ctx.lossy(LossKind::DatetimeAsString);
+                    Yaml::String(dt.to_string())
+                }
+            },
+            Self::Array(a) => Yaml::Array({
+                let mut array = Vec::with_capacity(a.len());
+                for (i, val) in a.into_iter().enumerate() {
+                    array.push(ctx.at(Segment::Index(i), |ctx| val.yaml_value(ctx))?);
+                }
+                array
+            }),
             Self::Table(t) => Yaml::Hash({
                 let mut hash = Hash::with_capacity(t.items.capacity());
+                let mut typed_keys = t.typed_keys;
                 for (name, val) in t.items {
-                    hash.insert(Yaml::String(name), val.into());
+                    let val = ctx.at(Segment::Key(name.clone()), |ctx| val.yaml_value(ctx))?;
+                    let key = match typed_keys.remove(&name) {
+                        Some(key) => key.yaml_value(ctx)?,
+                        None => Yaml::String(name),
+                    };
+                    hash.insert(key, val);
                 }
                 hash
-            })
-        }
+            }),
+        })
     }
 }
 
-impl TryFrom<yaml::Yaml> for Value {
+#[cfg(feature = "yaml")]
+impl TryFrom<Value> for yaml::Yaml {
     type Error = Error;
-    fn try_from(value: yaml::Yaml) -> Result<Self, Self::Error> {
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.into_yaml(&Options::default()).map(|(value, _)| value)
+    }
+}
+
+/// Parses a YAML real, including the `.inf`/`.nan` spellings and the `_`
+/// digit separators YAML 1.1 allows.
+#[cfg(feature = "yaml")]
+fn parse_yaml_float(s: &str) -> Result<f64, Error> {
+    match s {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Ok(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Ok(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => Ok(f64::NAN),
+        _ => s.replace('_', "").parse().map_err(|_| {
+            Error::invalid(Format::Yaml, &Path::new(), format!("'{s}' is not a float"))
+        }),
+    }
+}
+
+/// What to do with mapping keys that are not strings, like `1: one` or
+/// `true: yes`. Only scalar keys are accepted; complex keys are always an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyPolicy {
+    /// Use the key's text, e.g. `1` becomes `"1"` and `~` becomes `"null"`.
+    #[default]
+    Stringify,
+    Error,
+    /// Stringify the key, but remember its type in [`Table::typed_keys`] so
+    /// YAML output can write it back unchanged.
+    Keep,
+}
+
+impl std::str::FromStr for KeyPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stringify" => Ok(Self::Stringify),
+            "error" => Ok(Self::Error),
+            "keep" => Ok(Self::Keep),
+            _ => Err(format!("unknown key policy '{s}'")),
+        }
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl Value {
+    pub fn from_yaml(value: yaml::Yaml, keys: KeyPolicy) -> Result<Self, Error> {
         use yaml::Yaml;
         Ok(match value {
-            Yaml::Alias(_) => return Err(Error::UnsupportedType("alias")),
-            Yaml::BadValue => return Err(Error::InvalidValue(format!("{value:?}"))),
+            // Only produced by hand, the loader expands aliases as it goes.
+            Yaml::Alias(_) => {
+                return Err(Error::invalid(
+                    Format::Yaml,
+                    &Path::new(),
+                    "unresolved alias",
+                ))
+            }
+            Yaml::BadValue => {
+                return Err(Error::invalid(
+                    Format::Yaml,
+                    &Path::new(),
+                    "malformed scalar",
+                ))
+            }
             Yaml::Null => Self::Null,
             Yaml::Integer(i) if i >= 0 => Self::UInt(i as u64),
             Yaml::Integer(i) => Self::Int(i),
-            Yaml::Real(fs) => Self::Float(fs.parse().unwrap()),
+            // yaml_rust reads integers above `i64::MAX` as reals.
+            Yaml::Real(fs) => match fs.parse::<u64>() {
+                Ok(i) => Self::UInt(i),
+                // The loader reads plain timestamps as reals, see `yaml_loader`.
+                Err(_) => match fs.parse::<Datetime>() {
+                    Ok(dt) => Self::Datetime(dt),
+                    Err(_) => Self::Float(parse_yaml_float(&fs)?),
+                },
+            },
             Yaml::String(s) => Self::String(s),
             Yaml::Boolean(b) => Self::Bool(b),
             Yaml::Array(a) => Self::Array(
                 a.into_iter()
-                    .map(TryInto::try_into)
-                    .collect::<Result<Vec<Self>, Self::Error>>()?,
+                    .enumerate()
+                    .map(|(i, v)| Self::from_yaml(v, keys).map_err(|e| e.within(Segment::Index(i))))
+                    .collect::<Result<Vec<Self>, Error>>()?,
             ),
-            Yaml::Hash(h) => Self::Table(
-                Table {
-                    items: {
-                        let mut items = HashMap::with_capacity(h.capacity());
-                        for (key, val) in h {
-                            match key.as_str() {
-                                Some(key) => {
-                                    items.insert(key.to_owned(), val.try_into()?);
-                                },
-                                None => return Err(Error::InvalidValue(format!("{key:?}")))
+            Yaml::Hash(h) => Self::Table({
+                let mut table = Table::with_capacity(h.len());
+                for (key, val) in h {
+                    let name = match &key {
+                        Yaml::String(s) => s.clone(),
+                        Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_) | Yaml::Null
+                            if keys != KeyPolicy::Error =>
+                        {
+                            let typed = Self::from_yaml(key, keys)?;
+                            let name = typed.key_string();
+                            if keys == KeyPolicy::Keep {
+                                table.typed_keys.insert(name.clone(), typed);
                             }
+                            name
+                        }
+                        _ => {
+                            return Err(Error::invalid(
+                                Format::Yaml,
+                                &Path::new(),
+                                format!("unsupported key {key:?}"),
+                            ))
                         }
-                        items
+                    };
+                    // Only possible when a stringified key matches another key.
+                    if table.items.contains_key(&name) {
+                        return Err(Error::invalid(
+                            Format::Yaml,
+                            &Path::new(),
+                            "key is used twice once keys are stringified",
+                        )
+                        .within(Segment::Key(name)));
                     }
-            })
+                    let val = Self::from_yaml(val, keys)
+                        .map_err(|e| e.within(Segment::Key(name.clone())))?;
+                    table.items.insert(name, val);
+                }
+                table
+            }),
         })
     }
-}
\ No newline at end of file
+
+    /// The text of a scalar used as a mapping key.
+    fn key_string(&self) -> String {
+        match self {
+            Self::Null => "null".to_owned(),
+            Self::Bool(b) => b.to_string(),
+            Self::Int(i) => i.to_string(),
+            Self::UInt(i) => i.to_string(),
+            Self::Float(f) => f.to_string(),
+            Self::String(s) => s.clone(),
+            Self::Datetime(dt) => dt.to_string(),
+            Self::Array(_) | Self::Table(_) => unreachable!("only scalars are used as keys"),
+        }
+    }
+}
+
+/// The name a scalar YAML key gets in a [`Table`], or `None` for keys that
+/// are collections.
+#[cfg(feature = "yaml")]
+pub(crate) fn yaml_key_name(key: &yaml::Yaml) -> Option<String> {
+    match key {
+        yaml::Yaml::String(s) => Some(s.clone()),
+        yaml::Yaml::Array(_) | yaml::Yaml::Hash(_) => None,
+        _ => Value::from_yaml(key.clone(), KeyPolicy::Stringify)
+            .ok()
+            .map(|val| val.key_string()),
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl TryFrom<yaml::Yaml> for Value {
+    type Error = Error;
+    fn try_from(value: yaml::Yaml) -> Result<Self, Self::Error> {
+        Self::from_yaml(value, KeyPolicy::default())
+    }
+}
+
+/***********************************************/
+// INI
+
+/// What to do with values INI has no way to express: arrays, tables nested
+/// below a section, and null.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IniPolicy {
+    #[default]
+    Error,
+    /// Leave the value out of the output.
+    Skip,
+    /// Arrays become repeated keys, nested tables become dotted section
+    /// names (`[server.tls]`) and null becomes an empty value.
+    Flatten,
+}
+
+impl std::str::FromStr for IniPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "skip" => Ok(Self::Skip),
+            "flatten" => Ok(Self::Flatten),
+            _ => Err(format!("unknown INI policy '{s}'")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IniOptions {
+    pub policy: IniPolicy,
+    /// Read `true`, `false` and numbers as typed values instead of strings.
+    pub infer_types: bool,
+}
+
+#[cfg(feature = "ini")]
+impl Value {
+    pub fn from_ini_str(s: &str, infer_types: bool) -> Self {
+        if !infer_types {
+            return Self::String(s.to_owned());
+        }
+        match s {
+            "true" => return Self::Bool(true),
+            "false" => return Self::Bool(false),
+            _ => {}
+        }
+        if let Ok(i) = s.parse::<u64>() {
+            Self::UInt(i)
+        } else if let Ok(i) = s.parse::<i64>() {
+            Self::Int(i)
+        } else {
+            // `f64::from_str` also takes "inf" and "NaN", which are much more
+            // likely to be plain words in an INI file.
+            match s.parse::<f64>() {
+                Ok(f) if s.contains(|c: char| c.is_ascii_digit()) => Self::Float(f),
+                _ => Self::String(s.to_owned()),
+            }
+        }
+    }
+
+    /// Fails if a key outside of any section has the name of a section,
+    /// as both would be the same key of the table.
+    pub fn from_ini(ini: &ini::Ini, options: &IniOptions) -> Result<Self, Error> {
+        fn read(props: &ini::Properties, table: &mut Table, infer_types: bool) {
+            for (key, val) in props {
+                let val = Value::from_ini_str(val, infer_types);
+                match table.items.get_mut(key) {
+                    Some(Value::Array(a)) => a.push(val),
+                    Some(prev) => *prev = Value::Array(vec![std::mem::take(prev), val]),
+                    None => {
+                        table.items.insert(key.to_owned(), val);
+                    }
+                }
+            }
+        }
+
+        let collision = |name: &str| {
+            Error::invalid(
+                Format::Ini,
+                &Path::new(),
+                "key is also the name of a section",
+            )
+            .within(Segment::Key(name.to_owned()))
+        };
+
+        let mut table = Table::default();
+        for (section, props) in ini {
+            match section {
+                None => {
+                    let mut keys = props.iter().map(|(key, _)| key);
+                    if let Some(key) =
+                        keys.find(|key| matches!(table.items.get(*key), Some(Self::Table(_))))
+                    {
+                        return Err(collision(key));
+                    }
+                    read(props, &mut table, options.infer_types);
+                }
+                Some(name) => {
+                    let entry = table
+                        .items
+                        .entry(name.to_owned())
+                        .or_insert_with(|| Self::Table(Table::default()));
+                    let Self::Table(section) = entry else {
+                        return Err(collision(name));
+                    };
+                    read(props, section, options.infer_types);
+                }
+            }
+        }
+        Ok(Self::Table(table))
+    }
+}
+
+#[cfg(feature = "ini")]
+impl Value {
+    fn into_ini_str(self, ctx: &mut Ctx, policy: IniPolicy) -> Result<Option<String>, Error> {
+        let unsupported = |ctx: &Ctx, what| Error::unsupported(Format::Ini, &ctx.path, what);
+        let (name, s) = match self {
+            Self::String(s) => {
+                if s.trim() != s {
+                    ctx.lossy(LossKind::Trimmed);
+                } else if s.starts_with(['"', '\'']) {
+                    ctx.lossy(LossKind::LeadingQuote);
+                }
+                return Ok(Some(s));
+            }
+            Self::Bool(b) => ("boolean", b.to_string()),
+            Self::Int(i) => ("integer", i.to_string()),
+            Self::UInt(i) => ("integer", i.to_string()),
+            Self::Float(f) => ("float", f.to_string()),
+            Self::Datetime(dt) => {
+                ctx.lossy(LossKind::DatetimeAsString);
+                return Ok(Some(dt.to_string()));
+            }
+            Self::Null => match policy {
+                IniPolicy::Error => return Err(unsupported(ctx, Unsupported::Null)),
+                IniPolicy::Skip => {
+                    ctx.lossy(LossKind::NullDropped);
+                    return Ok(None);
+                }
+                IniPolicy::Flatten => {
+                    ctx.lossy(LossKind::NullReplaced(String::new()));
+                    return Ok(Some(String::new()));
+                }
+            },
+            Self::Array(_) => return Err(unsupported(ctx, Unsupported::NestedArray)),
+            Self::Table(_) => return Err(unsupported(ctx, Unsupported::TableInArray)),
+        };
+        ctx.lossy(LossKind::ScalarAsString(name));
+        Ok(Some(s))
+    }
+
+    pub fn into_ini(self, options: &IniOptions) -> Result<(ini::Ini, ConversionReport), Error> {
+        fn write(
+            ini: &mut ini::Ini,
+            section: Option<&str>,
+            table: Table,
+            policy: IniPolicy,
+            ctx: &mut Ctx,
+        ) -> Result<(), Error> {
+            let mut subtables = vec![];
+            for (key, val) in table.items {
+                ctx.path.push(Segment::Key(key.clone()));
+                if table.typed_keys.contains_key(&key) {
+                    ctx.lossy(LossKind::KeyAsString);
+                }
+                let unsupported =
+                    |ctx: &Ctx, what| Error::unsupported(Format::Ini, &ctx.path, what);
+                let as_section = matches!(val, Value::Table(_));
+                if let Some(why) = ini_key_problem(&key, as_section) {
+                    match policy {
+                        IniPolicy::Error => return Err(unsupported(ctx, Unsupported::Key(why))),
+                        IniPolicy::Skip | IniPolicy::Flatten => {
+                            ctx.lossy(LossKind::KeyDropped(why))
+                        }
+                    }
+                    ctx.path.pop();
+                    continue;
+                }
+                match val {
+                    Value::Table(t) => match (section, policy) {
+                        (None, _) => subtables.push((key.clone(), key, t)),
+                        (Some(_), IniPolicy::Error) => {
+                            return Err(unsupported(ctx, Unsupported::NestedTable))
+                        }
+                        (Some(_), IniPolicy::Skip) => ctx.lossy(LossKind::Dropped("table")),
+                        (Some(name), IniPolicy::Flatten) => {
+                            ctx.lossy(LossKind::TableAsSection);
+                            subtables.push((format!("{name}.{key}"), key, t))
+                        }
+                    },
+                    Value::Array(a) => match policy {
+                        IniPolicy::Error => return Err(unsupported(ctx, Unsupported::Array)),
+                        IniPolicy::Skip => ctx.lossy(LossKind::Dropped("array")),
+                        IniPolicy::Flatten => {
+                            ctx.lossy(LossKind::ArrayAsRepeatedKeys);
+                            let props = ini
+                                .entry(section.map(str::to_owned))
+                                .or_insert(Default::default());
+                            for (i, item) in a.into_iter().enumerate() {
+                                let item = ctx
+                                    .at(Segment::Index(i), |ctx| item.into_ini_str(ctx, policy))?;
+                                if let Some(item) = item {
+                                    props.append(key.clone(), item);
+                                }
+                            }
+                        }
+                    },
+                    val => {
+                        if let Some(val) = val.into_ini_str(ctx, policy)? {
+                            ini.with_section(section).set(key, val);
+                        }
+                    }
+                }
+                ctx.path.pop();
+            }
+            for (name, key, t) in subtables {
+                ini.entry(Some(name.clone())).or_insert(Default::default());
+                ctx.at(Segment::Key(key), |ctx| {
+                    write(ini, Some(&name), t, policy, ctx)
+                })?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::Table(t) => {
+                // INI output has no settings of its own in `Options`.
+                let defaults = Options::default();
+                let mut ctx = Ctx::new(&defaults);
+                let mut ini = ini::Ini::new();
+                write(&mut ini, None, t, options.policy, &mut ctx)?;
+                Ok((ini, ctx.report))
+            }
+            _ => Err(Error::NotATable(Format::Ini)),
+        }
+    }
+}
+
+/// Why INI can not hold `key`, or `key` as a section name, when it would read
+/// back as another key or not at all.
+#[cfg(feature = "ini")]
+fn ini_key_problem(key: &str, section: bool) -> Option<&'static str> {
+    Some(if key.is_empty() {
+        "an empty key"
+    } else if key.trim() != key {
+        "a key with whitespace at either end"
+    } else if key.contains(char::is_control) {
+        "a key with a control character in it"
+    } else if section && key.contains(']') {
+        "a section name with `]` in it"
+    } else if !section && key.contains(['=', ':']) {
+        "a key with `=` or `:` in it"
+    } else if !section && key.starts_with(['#', ';', '[']) {
+        "a key starting with `#`, `;` or `[`"
+    } else {
+        return None;
+    })
+}
+
+#[cfg(feature = "ini")]
+impl TryFrom<ini::Ini> for Value {
+    type Error = Error;
+    fn try_from(value: ini::Ini) -> Result<Self, Self::Error> {
+        Self::from_ini(&value, &IniOptions::default())
+    }
+}
+
+#[cfg(feature = "ini")]
+impl TryFrom<Value> for ini::Ini {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.into_ini(&IniOptions::default()).map(|(ini, _)| ini)
+    }
+}
@@ -0,0 +1,470 @@
+use std::fmt::Write;
+
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+use crate::comment::{Comment, Comments};
+use crate::path::{Path, Segment};
+use crate::report::{ConversionReport, LossKind, Lossy};
+use crate::style::{KeyQuotes, StringQuotes, StyleOptions, YamlStyle};
+use crate::value::yaml_key_name;
+use crate::yaml_loader::plain;
+
+/// Writes `doc` with `comments` above and after the values they belong to.
+///
+/// Block style follows `yaml_rust::YamlEmitter`, except that strings that
+/// would read back as timestamps, like `"1979-05-27"`, are quoted. Comments
+/// that can not be written, in compact output, are listed in the report.
+pub fn emit(doc: &Yaml, comments: &Comments, style: &StyleOptions) -> (String, ConversionReport) {
+    let mut emitter = Emitter {
+        out: String::from("---\n"),
+        level: -1,
+        path: Path::new(),
+        comments,
+        style,
+        // Nesting is all YAML has to tell levels apart.
+        indent: " ".repeat(style.indent.unwrap_or(2).max(1)),
+        report: ConversionReport::default(),
+    };
+    if style.compact || style.yaml == YamlStyle::Flow {
+        emitter.level = 0;
+        emitter.emit_flow(doc);
+    } else {
+        emitter.write_leading_inside(doc);
+        emitter.emit_node(doc);
+    }
+    (emitter.out, emitter.report)
+}
+
+struct Emitter<'a> {
+    out: String,
+    level: isize,
+    /// The path of the node being written.
+    path: Path,
+    comments: &'a Comments,
+    style: &'a StyleOptions,
+    indent: String,
+    report: ConversionReport,
+}
+
+// Writing to a `String` never fails.
+impl Emitter<'_> {
+    fn comment(&self) -> Option<&Comment> {
+        self.comments.get(&self.path)
+    }
+
+    /// Whether anything inside the current node has a comment.
+    fn comments_inside(&self) -> bool {
+        let depth = self.path.segments().len();
+        self.comments
+            .keys()
+            .any(|path| path.segments().len() > depth && path.starts_with(&self.path))
+    }
+
+    fn column(&self) -> usize {
+        let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line..].chars().count()
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.level.max(0) {
+            self.out.push_str(&self.indent);
+        }
+    }
+
+    /// Writes the comments above the current node, each on a line of its own.
+    fn write_before(&mut self) {
+        let comments = self.comments;
+        let Some(comment) = comments.get(&self.path) else {
+            return;
+        };
+        for line in &comment.before {
+            self.write_indent();
+            match line.is_empty() {
+                true => self.out.push('#'),
+                false => write!(self.out, "# {line}").unwrap(),
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn write_after(&mut self) {
+        let comments = self.comments;
+        if let Some(after) = comments.get(&self.path).and_then(|c| c.after.as_ref()) {
+            match after.is_empty() {
+                true => self.out.push_str(" #"),
+                false => write!(self.out, " # {after}").unwrap(),
+            }
+        }
+    }
+
+    /// Writes the comments above the first node in a collection, and above
+    /// the nodes that share its line in compact notation, like the `name` in
+    /// `- name: web`.
+    fn write_leading_inside(&mut self, node: &Yaml) {
+        let (segment, first) = match node {
+            Yaml::Array(v) if !v.is_empty() => (Segment::Index(0), &v[0]),
+            Yaml::Hash(h) if !h.is_empty() => {
+                let (k, v) = h.front().unwrap();
+                (key_segment(k), v)
+            }
+            _ => return,
+        };
+        self.path.push(segment);
+        self.write_before();
+        if matches!(node, Yaml::Array(_)) && self.is_compact(first) {
+            self.write_leading_inside(first);
+        }
+        self.path.pop();
+    }
+
+    /// Whether the current node, an item of an array, starts on the line of
+    /// its `-`. A comment after the item moves it to the next line, since
+    /// it has to go right after the `-`, and so does an indent too narrow
+    /// for the `-` and a space.
+    fn is_compact(&self, node: &Yaml) -> bool {
+        let non_empty = match node {
+            Yaml::Array(v) => !v.is_empty(),
+            Yaml::Hash(h) => !h.is_empty(),
+            _ => false,
+        };
+        non_empty
+            && self.indent.len() >= 2
+            && self.comment().and_then(|c| c.after.as_ref()).is_none()
+    }
+
+    fn emit_node(&mut self, node: &Yaml) {
+        match node {
+            Yaml::Array(v) => self.emit_array(v),
+            Yaml::Hash(h) => self.emit_hash(h),
+            scalar => write_scalar(&mut self.out, scalar, self.style),
+        }
+    }
+
+    fn emit_array(&mut self, v: &[Yaml]) {
+        if v.is_empty() {
+            self.out.push_str("[]");
+            return;
+        }
+        self.level += 1;
+        for (i, x) in v.iter().enumerate() {
+            self.path.push(Segment::Index(i));
+            if i > 0 {
+                self.out.push('\n');
+                self.write_before();
+                if self.is_compact(x) {
+                    self.write_leading_inside(x);
+                }
+                self.write_indent();
+            }
+            self.out.push('-');
+            self.emit_val(true, x);
+            self.path.pop();
+        }
+        self.level -= 1;
+    }
+
+    fn emit_hash(&mut self, h: &Hash) {
+        if h.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.level += 1;
+        for (i, (k, v)) in h.iter().enumerate() {
+            self.path.push(key_segment(k));
+            if i > 0 {
+                self.out.push('\n');
+                self.write_before();
+                self.write_indent();
+            }
+            if matches!(k, Yaml::Hash(_) | Yaml::Array(_)) {
+                self.out.push('?');
+                self.emit_val(true, k);
+                self.out.push('\n');
+                self.write_indent();
+                self.out.push(':');
+                self.emit_val(true, v);
+            } else {
+                write_key(&mut self.out, k, self.style);
+                self.out.push(':');
+                self.emit_val(false, v);
+            }
+            self.path.pop();
+        }
+        self.level -= 1;
+    }
+
+    /// Writes a value that follows a `:` or `-`, either on the same line or,
+    /// for collections that can not be `inline`, on the next.
+    fn emit_val(&mut self, inline: bool, val: &Yaml) {
+        let empty = match val {
+            Yaml::Array(v) => v.is_empty(),
+            Yaml::Hash(h) => h.is_empty(),
+            _ => {
+                self.out.push(' ');
+                self.emit_node(val);
+                self.write_after();
+                return;
+            }
+        };
+        if !empty && self.style.line_width.is_some() && !self.comments_inside() {
+            let mut flat = String::new();
+            self.write_flat(&mut flat, val);
+            if self.style.fits(self.column() + 1, flat.chars().count()) {
+                self.out.push(' ');
+                self.out.push_str(&flat);
+                self.write_after();
+                return;
+            }
+        }
+        if empty {
+            self.out.push(' ');
+            self.emit_node(val);
+            self.write_after();
+        } else if inline && self.is_compact(val) {
+            // What follows the `-` lines up with the next level.
+            self.out.push_str(&self.indent[1..]);
+            self.emit_node(val);
+        } else {
+            self.write_after();
+            self.out.push('\n');
+            self.level += 1;
+            self.write_leading_inside(val);
+            self.write_indent();
+            self.level -= 1;
+            self.emit_node(val);
+        }
+    }
+
+    /// Writes `node` in flow style, like JSON: on one line if it fits, or
+    /// with an entry per line and comments.
+    fn emit_flow(&mut self, node: &Yaml) -> bool {
+        let entries = match node {
+            Yaml::Array(v) if !v.is_empty() => v.len(),
+            Yaml::Hash(h) if !h.is_empty() => h.len(),
+            Yaml::Array(_) => {
+                self.out.push_str("[]");
+                return false;
+            }
+            Yaml::Hash(_) => {
+                self.out.push_str("{}");
+                return false;
+            }
+            node => {
+                write_scalar(&mut self.out, node, self.style);
+                return false;
+            }
+        };
+        if self.style.compact || self.style.line_width.is_some() && !self.comments_inside() {
+            let mut flat = String::new();
+            self.write_flat(&mut flat, node);
+            if self.style.fits(self.column(), flat.chars().count() + 1) {
+                self.out.push_str(&flat);
+                return false;
+            }
+        }
+        let (open, close) = match node {
+            Yaml::Array(_) => ('[', ']'),
+            _ => ('{', '}'),
+        };
+        self.out.push(open);
+        self.write_after();
+        self.level += 1;
+        for i in 0..entries {
+            let (key, val) = match node {
+                Yaml::Array(v) => (None, &v[i]),
+                Yaml::Hash(h) => {
+                    let (k, v) = h.iter().nth(i).unwrap();
+                    (Some(k), v)
+                }
+                _ => unreachable!("only collections have entries"),
+            };
+            self.path.push(key.map_or(Segment::Index(i), key_segment));
+            self.out.push('\n');
+            self.write_before();
+            self.write_indent();
+            if let Some(key) = key {
+                self.write_flow_key(key);
+            }
+            let multi_line = self.emit_flow(val);
+            if i + 1 < entries {
+                self.out.push(',');
+            }
+            if !multi_line {
+                self.write_after();
+            }
+            self.path.pop();
+        }
+        self.level -= 1;
+        self.out.push('\n');
+        self.write_indent();
+        self.out.push(close);
+        true
+    }
+
+    fn write_flow_key(&mut self, key: &Yaml) {
+        match key {
+            Yaml::Array(_) | Yaml::Hash(_) => {
+                let mut flat = String::new();
+                self.write_flat(&mut flat, key);
+                self.out.push_str(&flat);
+            }
+            key => write_key(&mut self.out, key, self.style),
+        }
+        self.out.push_str(": ");
+    }
+
+    /// Writes `node` as one line of flow style. Comments inside it are
+    /// left out, and noted in the report.
+    fn write_flat(&mut self, out: &mut String, node: &Yaml) {
+        let space = if self.style.compact { "" } else { " " };
+        match node {
+            Yaml::Array(v) => {
+                out.push('[');
+                for (i, x) in v.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                        out.push_str(space);
+                    }
+                    self.path.push(Segment::Index(i));
+                    self.flat_dropped();
+                    self.write_flat(out, x);
+                    self.path.pop();
+                }
+                out.push(']');
+            }
+            Yaml::Hash(h) => {
+                out.push('{');
+                for (i, (k, v)) in h.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                        out.push_str(space);
+                    }
+                    self.path.push(key_segment(k));
+                    self.flat_dropped();
+                    match k {
+                        Yaml::Array(_) | Yaml::Hash(_) => self.write_flat(out, k),
+                        k => write_key(out, k, self.style),
+                    }
+                    // The space after a `:` is what makes it a mapping.
+                    out.push_str(": ");
+                    self.write_flat(out, v);
+                    self.path.pop();
+                }
+                out.push('}');
+            }
+            scalar => write_scalar(out, scalar, self.style),
+        }
+    }
+
+    fn flat_dropped(&mut self) {
+        if self.comment().is_some() {
+            self.report.entries.push(Lossy {
+                path: self.path.clone(),
+                kind: LossKind::Dropped("comment"),
+            });
+        }
+    }
+}
+
+/// The path segment of a mapping key. Keys that are collections, which
+/// never come from a [`Table`](crate::table::Table), get an empty one.
+fn key_segment(key: &Yaml) -> Segment {
+    Segment::Key(yaml_key_name(key).unwrap_or_default())
+}
+
+/// Writes a scalar, quoting strings as the style asks, or when they would
+/// not read back as the same string bare.
+fn write_scalar(out: &mut String, node: &Yaml, style: &StyleOptions) {
+    match node {
+        Yaml::String(v) => write_str(
+            out,
+            v,
+            style.string_quotes != StringQuotes::Minimal,
+            style.string_quotes == StringQuotes::Single,
+        ),
+        Yaml::Boolean(v) => write!(out, "{v}").unwrap(),
+        Yaml::Integer(v) => write!(out, "{v}").unwrap(),
+        Yaml::Real(v) => out.push_str(v),
+        Yaml::Null | Yaml::BadValue => out.push('~'),
+        Yaml::Array(_) | Yaml::Hash(_) | Yaml::Alias(_) => {}
+    }
+}
+
+fn write_key(out: &mut String, key: &Yaml, style: &StyleOptions) {
+    match key {
+        Yaml::String(v) => write_str(
+            out,
+            v,
+            style.key_quotes == KeyQuotes::Always,
+            style.string_quotes == StringQuotes::Single,
+        ),
+        key => write_scalar(out, key, style),
+    }
+}
+
+fn write_str(out: &mut String, v: &str, always: bool, single: bool) {
+    if !always && !need_quotes(v) {
+        out.push_str(v);
+    } else if single && !v.contains(|c: char| c.is_control() && c != '\t') {
+        write!(out, "'{}'", v.replace('\'', "''")).unwrap();
+    } else {
+        escape_str(out, v);
+    }
+}
+
+/// Copied from `yaml_rust`, whose emitter this follows.
+fn escape_str(out: &mut String, v: &str) {
+    out.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\x08' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\x0c' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            '\0'..='\x1f' | '\x7f' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Whether a string would read back as something else, or not at all, if
+/// written bare: when it is not a plain scalar as `yaml_rust` writes them, or
+/// when the loader would read it, in any case, as another type. Other readers
+/// take `Null`, `TRUE` and `.NaN` for what they look like, and YAML 1.1 ones
+/// also `yes`, `no`, `on` and `off`.
+fn need_quotes(string: &str) -> bool {
+    string.is_empty()
+        || string.starts_with(' ')
+        || string.ends_with(' ')
+        || string.starts_with(['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
+        || string.contains(|c: char| {
+            matches!(
+                c,
+                ':' | '{'
+                    | '}'
+                    | '['
+                    | ']'
+                    | ','
+                    | '#'
+                    | '`'
+                    | '"'
+                    | '\''
+                    | '\\'
+                    | '\0'..='\x06'
+                    | '\t'
+                    | '\n'
+                    | '\r'
+                    | '\x0e'..='\x1a'
+                    | '\x1c'..='\x1f'
+            )
+        })
+        // `y` and `n` are left bare, as in libyaml.
+        || ["yes", "Yes", "YES", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF"]
+            .contains(&string)
+        || !matches!(plain(&string.to_ascii_lowercase()), Yaml::String(_))
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+use toml_datetime::Datetime;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{ScanError, Yaml};
+
+use crate::options::LoadOptions;
+use crate::path::{Path, Segment};
+use crate::value::yaml_key_name;
+
+/// Messages leave out the position, see [`LoadError::position`].
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("{}", scan_message(.0))]
+    Scan(#[from] ScanError),
+    #[error("Aliases expand to more than {0} nodes")]
+    AliasLimit(usize),
+    #[error("Unknown anchor")]
+    UnknownAnchor { line: usize, col: usize },
+    #[error("Merge key needs a mapping or a list of mappings")]
+    InvalidMerge { line: usize, col: usize },
+}
+
+impl LoadError {
+    /// The line and column of the error, both counting from 1.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Scan(e) => Some((e.marker().line(), e.marker().col() + 1)),
+            Self::AliasLimit(_) => None,
+            Self::UnknownAnchor { line, col } | Self::InvalidMerge { line, col } => {
+                Some((*line, *col))
+            }
+        }
+    }
+}
+
+/// `ScanError` only exposes its message as part of its `Display`.
+fn scan_message(e: &ScanError) -> String {
+    let message = e.to_string();
+    let suffix = format!(
+        " at line {} column {}",
+        e.marker().line(),
+        e.marker().col() + 1
+    );
+    match message.strip_suffix(&suffix) {
+        Some(message) => message.to_owned(),
+        None => message,
+    }
+}
+
+/// A stand-in for `YamlLoader::load_from_str` that resolves aliases under a
+/// size limit and applies `<<` merge keys.
+///
+/// `YamlLoader` copies every alias eagerly, so a "billion laughs" document
+/// exhausts memory before any of its output can be looked at.
+pub fn load(source: &str, options: &LoadOptions) -> Result<Vec<Yaml>, LoadError> {
+    run(source, options, false).map(|loader| loader.docs)
+}
+
+/// Like [`load`], but also returns the line each value starts on, by path,
+/// for each document. Keys count as the start of their entry. Values that
+/// come from an alias have no line of their own and are left out.
+#[allow(clippy::type_complexity)]
+pub fn load_with_lines(
+    source: &str,
+    options: &LoadOptions,
+) -> Result<(Vec<Yaml>, Vec<Vec<(Path, usize)>>), LoadError> {
+    run(source, options, true).map(|loader| (loader.docs, loader.doc_lines))
+}
+
+fn run<'a>(source: &str, options: &'a LoadOptions, lines: bool) -> Result<Loader<'a>, LoadError> {
+    let mut loader = Loader {
+        options,
+        docs: vec![],
+        root: None,
+        stack: vec![],
+        anchors: HashMap::new(),
+        expanded: 0,
+        error: None,
+        lines: lines.then(Vec::new),
+        doc_lines: vec![],
+    };
+    Parser::new(source.chars()).load(&mut loader, true)?;
+    match loader.error.take() {
+        Some(e) => Err(e),
+        None => Ok(loader),
+    }
+}
+
+enum Frame {
+    Array(Vec<Yaml>, usize),
+    Hash(MappingFrame, usize),
+}
+
+#[derive(Default)]
+struct MappingFrame {
+    hash: Hash,
+    /// The key waiting for its value, and whether it is a `<<` merge key.
+    key: Option<(Yaml, bool)>,
+    merges: Vec<Hash>,
+}
+
+impl MappingFrame {
+    /// Merged keys come first and never override keys written out in the
+    /// mapping itself; of several merged mappings, the first one wins.
+    fn finish(self) -> Hash {
+        if self.merges.is_empty() {
+            return self.hash;
+        }
+        let mut out = Hash::new();
+        for merged in self.merges {
+            for (key, val) in merged {
+                if !self.hash.contains_key(&key) && !out.contains_key(&key) {
+                    out.insert(key, val);
+                }
+            }
+        }
+        out.extend(self.hash);
+        out
+    }
+}
+
+struct Loader<'a> {
+    options: &'a LoadOptions,
+    docs: Vec<Yaml>,
+    root: Option<Yaml>,
+    stack: Vec<Frame>,
+    /// Anchored nodes with their size in nodes.
+    anchors: HashMap<usize, (Yaml, usize)>,
+    expanded: usize,
+    error: Option<LoadError>,
+    /// The lines of the values in the current document, when tracked.
+    lines: Option<Vec<(Path, usize)>>,
+    doc_lines: Vec<Vec<(Path, usize)>>,
+}
+
+fn node_count(node: &Yaml) -> usize {
+    match node {
+        Yaml::Array(a) => 1 + a.iter().map(node_count).sum::<usize>(),
+        Yaml::Hash(h) => {
+            1 + h
+                .iter()
+                .map(|(k, v)| node_count(k) + node_count(v))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+/// Resolves a scalar the same way `YamlLoader` does, except that plain
+/// timestamps like `1979-05-27T07:32:00Z` become reals instead of strings.
+/// `Yaml` has no timestamp type, and reals are the one variant kept as text
+/// that `Value::from_yaml` looks at again.
+fn scalar(value: String, style: TScalarStyle, tag: Option<TokenType>) -> Yaml {
+    if style != TScalarStyle::Plain {
+        return Yaml::String(value);
+    }
+    match tag {
+        Some(TokenType::Tag(handle, suffix)) if handle == "!!" => match suffix.as_str() {
+            "bool" => value.parse().map_or(Yaml::BadValue, Yaml::Boolean),
+            "int" => value.parse().map_or(Yaml::BadValue, Yaml::Integer),
+            "float" => Yaml::Real(value),
+            "null" => match value.as_str() {
+                "~" | "null" => Yaml::Null,
+                _ => Yaml::BadValue,
+            },
+            _ => Yaml::String(value),
+        },
+        Some(_) => Yaml::String(value),
+        None => plain(&value),
+    }
+}
+
+/// What a plain scalar without a tag reads as.
+pub(crate) fn plain(value: &str) -> Yaml {
+    match is_timestamp(value) {
+        true => Yaml::Real(value.to_owned()),
+        false => Yaml::from_str(value),
+    }
+}
+
+/// A YAML timestamp always has a date, unlike a TOML local time.
+pub(crate) fn is_timestamp(value: &str) -> bool {
+    value.starts_with(|c: char| c.is_ascii_digit())
+        && matches!(value.parse::<Datetime>(), Ok(dt) if dt.date.is_some())
+}
+
+impl Loader<'_> {
+    fn insert(&mut self, node: Yaml, anchor: usize, mark: Marker) -> Result<(), LoadError> {
+        self.insert_node(node, anchor, false, mark)
+    }
+
+    fn insert_node(
+        &mut self,
+        node: Yaml,
+        anchor: usize,
+        merge_key: bool,
+        mark: Marker,
+    ) -> Result<(), LoadError> {
+        if anchor > 0 {
+            let count = node_count(&node);
+            self.anchors.insert(anchor, (node.clone(), count));
+        }
+        match self.stack.last_mut() {
+            None => self.root = Some(node),
+            Some(Frame::Array(a, _)) => a.push(node),
+            Some(Frame::Hash(frame, _)) => match frame.key.take() {
+                None => frame.key = Some((node, merge_key)),
+                Some((_, true)) => match node {
+                    Yaml::Hash(h) => frame.merges.push(h),
+                    Yaml::Array(a) => {
+                        for item in a {
+                            match item {
+                                Yaml::Hash(h) => frame.merges.push(h),
+                                _ => return Err(invalid_merge(mark)),
+                            }
+                        }
+                    }
+                    _ => return Err(invalid_merge(mark)),
+                },
+                Some((key, false)) => {
+                    frame.hash.insert(key, node);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Notes the line of `node`, the next node to be inserted, if lines are
+    /// tracked. Only scalar nodes are passed in, the others start empty.
+    fn record(&mut self, node: Option<&Yaml>, merge_key: bool, mark: Marker) {
+        let Some((last, outer)) = self.stack.split_last() else {
+            return;
+        };
+        if self.lines.is_none() {
+            return;
+        }
+        let last = match last {
+            Frame::Array(a, _) => Some(Segment::Index(a.len())),
+            Frame::Hash(frame, _) if frame.key.is_none() && !merge_key => {
+                node.and_then(yaml_key_name).map(Segment::Key)
+            }
+            // Values are at the line of their key.
+            Frame::Hash(..) => return,
+        };
+        let path: Option<Path> = outer.iter().map(filling).chain([last]).collect();
+        if let (Some(lines), Some(path)) = (&mut self.lines, path) {
+            lines.push((path, mark.line()));
+        }
+    }
+
+    fn expects_key(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Hash(frame, _)) if frame.key.is_none())
+    }
+
+    fn handle(&mut self, ev: Event, mark: Marker) -> Result<(), LoadError> {
+        match ev {
+            Event::DocumentEnd => {
+                let doc = self.root.take().unwrap_or(Yaml::Null);
+                self.docs.push(doc);
+                if let Some(lines) = &mut self.lines {
+                    self.doc_lines.push(std::mem::take(lines));
+                }
+            }
+            Event::SequenceStart(anchor) => {
+                self.record(None, false, mark);
+                self.stack.push(Frame::Array(vec![], anchor))
+            }
+            Event::MappingStart(anchor) => {
+                self.record(None, false, mark);
+                self.stack
+                    .push(Frame::Hash(MappingFrame::default(), anchor))
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.stack.pop() {
+                Some(Frame::Array(a, anchor)) => self.insert(Yaml::Array(a), anchor, mark)?,
+                Some(Frame::Hash(frame, anchor)) => {
+                    self.insert(Yaml::Hash(frame.finish()), anchor, mark)?
+                }
+                None => unreachable!("the parser balances collection events"),
+            },
+            Event::Scalar(value, style, anchor, tag) => {
+                let merge_key = self.expects_key()
+                    && style == TScalarStyle::Plain
+                    && tag.is_none()
+                    && value == "<<";
+                let node = scalar(value, style, tag);
+                self.record(Some(&node), merge_key, mark);
+                self.insert_node(node, anchor, merge_key, mark)?;
+            }
+            Event::Alias(id) => {
+                let Some((node, count)) = self.anchors.get(&id) else {
+                    return Err(LoadError::UnknownAnchor {
+                        line: mark.line(),
+                        col: mark.col() + 1,
+                    });
+                };
+                self.expanded += count;
+                if self.expanded > self.options.alias_limit {
+                    return Err(LoadError::AliasLimit(self.options.alias_limit));
+                }
+                let node = node.clone();
+                self.insert(node, 0, mark)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The segment of the path a collection is about to fill in.
+fn filling(frame: &Frame) -> Option<Segment> {
+    match frame {
+        Frame::Array(a, _) => Some(Segment::Index(a.len())),
+        Frame::Hash(frame, _) => match &frame.key {
+            Some((key, false)) => yaml_key_name(key).map(Segment::Key),
+            _ => None,
+        },
+    }
+}
+
+fn invalid_merge(mark: Marker) -> LoadError {
+    LoadError::InvalidMerge {
+        line: mark.line(),
+        col: mark.col() + 1,
+    }
+}
+
+impl MarkedEventReceiver for Loader<'_> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.handle(ev, mark) {
+            self.error = Some(e);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Comments carried between formats with `truns convert --comments`.
+
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::corpus;
+
+fn convert(input: &str, from: &str, to: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_truns"))
+        .args(["convert", "--comments", "-f", from, "-t", to])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run truns");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{from} to {to} failed");
+    String::from_utf8(out.stdout).unwrap()
+}
+
+const YAML: &str = "\
+---
+# Deployment settings
+title: web # shown in the dashboard
+# Who to page
+owner:
+  name: Tom # on call
+  # ISO dates only
+  since: 1979-05-27
+database:
+  ports:
+    # primary
+    - 8000
+    - 8001
+servers:
+  # One entry per server
+  - name: alpha # first
+    ip: 10.0.0.1
+  # the backup
+  - name: beta
+";
+
+#[test]
+fn toml_comments_survive_yaml() {
+    let toml = std::fs::read_to_string(corpus("commented.toml")).unwrap();
+    let yaml = convert(&toml, "toml", "yaml");
+    assert_eq!(yaml, YAML);
+    // The comment above the first key of the second server moves up to its
+    // header, where YAML put it.
+    let expected = toml.replace("[[servers]]\n# the backup\n", "# the backup\n[[servers]]\n");
+    assert_eq!(convert(&yaml, "yaml", "toml"), expected);
+}
+
+#[test]
+fn yaml_comments_survive_yaml() {
+    assert_eq!(convert(YAML, "yaml", "yaml"), YAML);
+}
+
+#[test]
+fn comments_are_ignored_in_block_scalars_and_quotes() {
+    let input = "\
+text: |
+  # not a comment
+  body
+# above quoted
+quoted: \"a # b\"
+";
+    let expected = "\
+---
+text: \"# not a comment\\nbody\\n\"
+# above quoted
+quoted: \"a # b\"
+";
+    assert_eq!(convert(input, "yaml", "yaml"), expected);
+}
+
+#[test]
+fn jsonc_comments_become_yaml_comments() {
+    let jsonc = std::fs::read_to_string(corpus("settings.jsonc")).unwrap();
+    let yaml = "\
+---
+# Editor
+editor.tabSize: 2
+editor.rulers: # columns
+  - 80
+  - 120
+# Files
+files.exclude:
+  \"**/node_modules\": true
+";
+    assert_eq!(convert(&jsonc, "jsonc", "yaml"), yaml);
+    let json5 = "\
+{
+  // Editor
+  \"editor.tabSize\": 2,
+  \"editor.rulers\": [ // columns
+    80,
+    120
+  ],
+  // Files
+  \"files.exclude\": {
+    \"**/node_modules\": true
+  }
+}
+";
+    assert_eq!(convert(yaml, "yaml", "json5"), json5);
+}
@@ -0,0 +1,103 @@
+//! Guessing the format of input without an extension, with `truns::detect`
+//! and with `truns convert` on stdin.
+
+mod common;
+
+use truns::detect::{self, Confidence};
+use truns::Format;
+
+use common::truns;
+
+fn sniff(content: &str) -> Option<(Format, Confidence)> {
+    detect::sniff(content).map(|found| (found.format, found.confidence))
+}
+
+#[test]
+fn each_format_is_sniffed_with_a_confidence() {
+    for (content, format, confidence) in [
+        (r#"{"a": [1, 2]}"#, Format::Json, Confidence::High),
+        (
+            "{\"a\": 1}\n{\"a\": 2}\n",
+            Format::JsonLines,
+            Confidence::High,
+        ),
+        ("{\"a\": 1 /* note */}", Format::Jsonc, Confidence::Medium),
+        ("{a: 1}", Format::Json5, Confidence::Medium),
+        (
+            "name = \"app\"\n[server]\nport = 80\n",
+            Format::Toml,
+            Confidence::High,
+        ),
+        (
+            "name: app\nports: [80, 443]\n",
+            Format::Yaml,
+            Confidence::High,
+        ),
+        (
+            "; note\n[server]\nhost = a.example\n",
+            Format::Ini,
+            Confidence::High,
+        ),
+        ("host = a.example\n", Format::Ini, Confidence::Low),
+        ("just some words", Format::Yaml, Confidence::Low),
+        ("", Format::Toml, Confidence::Low),
+    ] {
+        assert_eq!(sniff(content), Some((format, confidence)), "{content:?}");
+    }
+}
+
+#[test]
+fn broken_json_and_yaml_are_not_taken_for_ini() {
+    for content in [r#"{"a":"#, "[1, 2", "[{\"a\": 1}]\n[", "{a: 1"] {
+        assert_eq!(sniff(content), None, "{content:?}");
+    }
+    assert_eq!(
+        sniff("a: 1\n b: 2\n"),
+        Some((Format::Ini, Confidence::Low)),
+        "YAML with a bad indent is only a weak guess at INI"
+    );
+    assert_eq!(
+        sniff("[server]\nhost = a.example\n").map(|(format, _)| format),
+        Some(Format::Ini),
+        "a `[section]` header does not look like JSON"
+    );
+}
+
+#[test]
+fn extensions_are_certain() {
+    let found = detect::detect(Some("app.yml".as_ref()), "{\"a\": 1}").unwrap();
+    assert_eq!(
+        (found.format, found.confidence),
+        (Format::Yaml, Confidence::Certain)
+    );
+    assert!(found.parsed.is_none());
+}
+
+#[test]
+fn cli_rejects_broken_json() {
+    let out = truns(&["convert", "-t", "json"], r#"{"a":"#);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: Could not detect the input format, pass it with --from\n"
+    );
+}
+
+#[test]
+fn cli_warns_about_weak_guesses() {
+    let out = truns(&["convert", "-t", "json"], "a: 1\n b: 2\n");
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "warning: guessed ini input with low confidence, pass --from to override\n"
+    );
+
+    let out = truns(&["convert", "-t", "json"], "[server]\nhost = a.example\n");
+    assert!(out.status.success());
+    assert!(out.stderr.is_empty());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\n  \"server\": {\n    \"host\": \"a.example\"\n  }\n}\n"
+    );
+}
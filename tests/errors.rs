@@ -0,0 +1,155 @@
+//! Errors for input that can not be read, from `Table::parse` and from
+//! `truns convert`, with the line and column of the problem if it does not
+//! parse.
+
+mod common;
+
+use std::error::Error as _;
+
+use truns::parse::{self, code_frame, Location};
+use truns::{Error, Format, Stream, Table};
+
+use common::truns;
+
+fn location(text: &str, format: Format) -> Location {
+    match Table::parse(text, format) {
+        Err(Error::Parse(e)) => e.location.expect("parse error without a location"),
+        other => panic!("{format}: expected a parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_errors_keep_the_parser_error() {
+    for (text, format) in [
+        ("{\"a\": }", Format::Json),
+        ("{a: }", Format::Json5),
+        ("a = = 1", Format::Toml),
+        ("a: [1", Format::Yaml),
+        ("[a\nb = 1", Format::Ini),
+    ] {
+        let err = Table::parse(text, format).unwrap_err();
+        assert!(matches!(err, Error::Parse(_)), "{format}: {err}");
+        assert!(err.source().is_some(), "{format}: {err}");
+    }
+}
+
+#[test]
+fn columns_count_characters() {
+    for (text, format, line, column) in [
+        ("{\"a\": 1,\n \"b\": }", Format::Json, 2, 7),
+        ("{\"name\": \"h\u{e9}llo\", \"x\": }", Format::Json, 1, 24),
+        ("{\"a\": 1}\n{\"\u{e9}\": x}\n", Format::JsonLines, 2, 7),
+        ("{\u{e9}: 1, b: @}", Format::Json5, 1, 11),
+        ("a = \"\u{e9}\"\nb = \"\u{e9}\" x\n", Format::Toml, 2, 9),
+        ("a: \u{e9}\nb: \"\u{e9}\" x\n", Format::Yaml, 2, 8),
+    ] {
+        let loc = location(text, format);
+        assert_eq!((loc.line, loc.column), (line, column), "{format}: {text}");
+    }
+}
+
+#[test]
+fn errors_at_the_end_point_past_the_last_line() {
+    let loc = location("{\"a\": 1,\n", Format::Json);
+    assert_eq!((loc.line, loc.column), (2, 1));
+    assert_eq!(loc.snippet, "  |\n2 | \n  | ^");
+}
+
+#[test]
+fn code_frames_put_a_caret_under_the_column() {
+    assert_eq!(
+        code_frame("[server]\nport = 80 80\n", 2, 11),
+        "  |\n2 | port = 80 80\n  |           ^"
+    );
+    assert_eq!(
+        code_frame("a:\n\tb: \u{e9}x\n", 2, 6),
+        "  |\n2 | \tb: \u{e9}x\n  | \t    ^",
+        "tabs are kept and characters count once"
+    );
+    let text = "\n".repeat(9) + "x = ?";
+    assert_eq!(
+        code_frame(&text, 10, 5),
+        "   |\n10 | x = ?\n   |     ^",
+        "the gutter is as wide as the line number"
+    );
+}
+
+#[test]
+fn cli_shows_where_parsing_failed() {
+    let out = truns(
+        &["convert", "-f", "json", "-t", "toml"],
+        "{\"name\": \"h\u{e9}llo\", \"x\": }",
+    );
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "\
+error: Failed to parse json: expected value
+ --> <stdin>:1:24
+  |
+1 | {\"name\": \"h\u{e9}llo\", \"x\": }
+  |                        ^
+"
+    );
+
+    let out = truns(
+        &["convert", "-f", "toml", "-t", "json"],
+        "[server]\nport = 80 80\n",
+    );
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "\
+error: Failed to parse toml: expected newline, `#`
+ --> <stdin>:2:11
+  |
+2 | port = 80 80
+  |           ^
+"
+    );
+}
+
+#[test]
+fn ini_keys_can_not_share_a_name_with_a_section() {
+    let err = Table::parse("a=1\n[a]\nb=2\n", Format::Ini).unwrap_err();
+    assert!(matches!(err, Error::Invalid { .. }), "{err}");
+    assert_eq!(
+        err.to_string(),
+        "a: Invalid ini value: key is also the name of a section"
+    );
+    assert!(Table::parse("a=1\n[b]\na=2\n", Format::Ini).is_ok());
+
+    let out = truns(&["convert", "-f", "ini", "-t", "json"], "a=1\n[a]\nb=2\n");
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: a: Invalid ini value: key is also the name of a section\n"
+    );
+}
+
+#[test]
+fn json_lines_errors_name_json_lines() {
+    let lines = parse::json_lines("{\"a\": 1}\n[1]\n").unwrap();
+    let err = Stream::from_json_lines(lines).unwrap_err();
+    assert_eq!(err.to_string(), "Document 1");
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Top level of the jsonl document is not a table"
+    );
+    let err = Stream::from_json_array(serde_json::json!([{"a": 1}, 2])).unwrap_err();
+    assert_eq!(err.to_string(), "Document 1");
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Top level of the json document is not a table"
+    );
+
+    let out = truns(
+        &["convert", "-f", "jsonl", "-t", "yaml"],
+        "{\"a\":1}\n[1]\n",
+    );
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: Document 1: Top level of the jsonl document is not a table\n"
+    );
+}
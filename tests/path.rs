@@ -0,0 +1,212 @@
+//! Getting and setting values by path, in the library and with `truns get`
+//! and `truns set`.
+
+mod common;
+
+use truns::path::Segment;
+use truns::{Error, Format, Path, StyleOptions, Table, Value};
+
+use common::{stdout, truns};
+
+const CONFIG: &str = r#"
+name = "app"
+"a.b" = 1
+
+[[servers]]
+host = "a.example"
+port = 80
+
+[[servers]]
+host = "b.example"
+"#;
+
+fn config() -> Table {
+    Table::parse(CONFIG, Format::Toml).unwrap()
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_owned())
+}
+
+#[test]
+fn paths_read_back_as_they_display() {
+    for text in [
+        "servers[0].host",
+        r#"servers[0]["tls.cert"]"#,
+        r#"["a b"].c"#,
+    ] {
+        assert_eq!(text.parse::<Path>().unwrap().to_string(), text);
+    }
+    let path: Path = r#"servers[0]."tls.cert""#.parse().unwrap();
+    assert_eq!(
+        path.segments(),
+        [
+            Segment::Key("servers".to_owned()),
+            Segment::Index(0),
+            Segment::Key("tls.cert".to_owned()),
+        ]
+    );
+    let path: Path = r#"["say \"hi\"\n"]"#.parse().unwrap();
+    assert_eq!(path.segments(), [Segment::Key("say \"hi\"\n".to_owned())]);
+    assert!("".parse::<Path>().unwrap().is_root());
+
+    for (text, message) in [
+        ("servers[", "expected an index or a quoted key in brackets"),
+        ("servers[0", "expected `]`"),
+        ("a..b", "empty key"),
+        (r#"a."b"#, "unterminated quoted key"),
+        ("a[0]b", "expected `.` or `[`"),
+    ] {
+        let err = text.parse::<Path>().unwrap_err();
+        assert!(matches!(err, Error::InvalidPath { .. }), "{text}");
+        assert!(err.to_string().ends_with(message), "{text}: {err}");
+    }
+}
+
+#[test]
+fn values_are_found_by_path() {
+    let mut table = config();
+    assert_eq!(
+        table.get_path("servers[1].host"),
+        Some(&string("b.example"))
+    );
+    assert_eq!(table.get_path(r#""a.b""#), Some(&Value::UInt(1)));
+    assert_eq!(table.get_path("servers[2].host"), None);
+    assert_eq!(table.get_path("name.first"), None);
+    assert_eq!(table.get_path("servers["), None);
+
+    let path: Path = "servers[0].port".parse().unwrap();
+    *table.get_path_mut(&path).unwrap() = Value::UInt(8080);
+    assert_eq!(table.get_path(path), Some(&Value::UInt(8080)));
+}
+
+#[test]
+fn setting_makes_missing_tables_and_arrays() {
+    let mut table = config();
+    let old = table.set_path("name", string("web")).unwrap();
+    assert_eq!(old, Some(string("app")));
+    assert_eq!(
+        table
+            .set_path("servers[2].host", string("c.example"))
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        table.set_path("tls.certs[0]", string("a.pem")).unwrap(),
+        None
+    );
+    let toml = table
+        .to_string(Format::Toml, &StyleOptions::default())
+        .unwrap();
+    assert_eq!(
+        toml,
+        r#"name = "web"
+"a.b" = 1
+
+[[servers]]
+host = "a.example"
+port = 80
+
+[[servers]]
+host = "b.example"
+
+[[servers]]
+host = "c.example"
+
+[tls]
+certs = [
+    "a.pem",
+]
+"#
+    );
+}
+
+#[test]
+fn failed_sets_change_nothing() {
+    let mut table = config();
+    for (path, message) in [
+        ("name.first", "Can not set name.first: name is a string, not a table"),
+        ("servers.host", "Can not set servers.host: servers is an array, not a table"),
+        ("servers[3]", "Can not set servers[3]: servers has 2 elements, so no index 3"),
+        (
+            "tls.certs[1]",
+            "Can not set tls.certs[1]: tls.certs is missing, and a new array starts at index 0, not 1",
+        ),
+        ("[0]", "Can not set [0]: the top level is a table, not an array"),
+    ] {
+        let err = table.set_path(path, Value::Null).unwrap_err();
+        assert_eq!(err.to_string(), message);
+    }
+    assert_eq!(table, config());
+}
+
+#[test]
+fn values_are_removed_by_path() {
+    let mut table = config();
+    assert_eq!(table.remove_path("servers[0].port"), Some(Value::UInt(80)));
+    assert_eq!(
+        table
+            .remove_path("servers[0]")
+            .and_then(Table::from)
+            .map(|t| t.items.len()),
+        Some(1)
+    );
+    assert_eq!(table.remove_path(r#"["a.b"]"#), Some(Value::UInt(1)));
+    assert_eq!(table.remove_path("servers[1]"), None);
+    assert_eq!(table.remove_path("missing.key"), None);
+    assert_eq!(
+        table.get_path("servers[0].host"),
+        Some(&string("b.example"))
+    );
+    assert_eq!(table.items.keys().collect::<Vec<_>>(), ["name", "servers"]);
+}
+
+#[test]
+fn cli_gets_values() {
+    let get = |path| stdout(truns(&["get", "-", path, "--from", "toml"], CONFIG));
+    assert_eq!(get("servers[0].host"), "a.example\n");
+    assert_eq!(get("servers[0].port"), "80\n");
+    assert_eq!(get("servers[1]"), "host = \"b.example\"\n");
+    assert!(get("servers").starts_with("[\n  {\n    \"host\": \"a.example\""));
+
+    let out = truns(&["get", "-", "servers[5]", "--from", "toml"], CONFIG);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: Nothing at servers[5]\n"
+    );
+}
+
+#[test]
+fn cli_gets_from_one_of_several_documents() {
+    let yaml = "name: a\n---\nname: b\n";
+    let out = stdout(truns(&["get", "-", "[1].name", "--from", "yaml"], yaml));
+    assert_eq!(out, "b\n");
+    let out = truns(&["get", "-", "name", "--from", "yaml"], yaml);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn cli_sets_values() {
+    let yaml = "# The servers\nservers:\n  - host: a.example\n";
+    let set = |args: &[&str]| {
+        let args = [&["set", "-"], args, &["--from", "yaml", "--comments"]].concat();
+        stdout(truns(&args, yaml))
+    };
+    assert_eq!(
+        set(&["servers[0].port", "8080"]),
+        "---\n# The servers\nservers:\n  - host: a.example\n    port: 8080\n"
+    );
+    assert_eq!(
+        set(&["servers[0].port", "8080", "--string"]),
+        "---\n# The servers\nservers:\n  - host: a.example\n    port: \"8080\"\n"
+    );
+    assert_eq!(
+        set(&["servers[1]", r#"{"host": "b.example"}"#]),
+        "---\n# The servers\nservers:\n  - host: a.example\n  - host: b.example\n"
+    );
+    assert_eq!(
+        set(&["name", "web server", "--to", "json"]),
+        "{\n  \"servers\": [\n    {\n      \"host\": \"a.example\"\n    }\n  ],\n  \"name\": \"web server\"\n}\n"
+    );
+}
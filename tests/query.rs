@@ -0,0 +1,267 @@
+//! Queries on values with `truns::Query`, and with `truns query` on documents
+//! of every format.
+
+mod common;
+
+use truns::{Error, Format, Query, Table, Value};
+
+use common::{stdout, truns};
+
+const CONFIG: &str = r#"
+name = "app"
+tags = ["web", "api", "web"]
+
+[[servers]]
+host = "a.example"
+port = 80
+
+[[servers]]
+host = "b.example"
+port = 8080
+
+[dependencies]
+serde = "1.0"
+clap = { version = "4", features = ["derive"] }
+"#;
+
+fn run(query: &str) -> Vec<Value> {
+    let input = Value::Table(Table::parse(CONFIG, Format::Toml).unwrap());
+    let query: Query = query.parse().unwrap();
+    query.run(&input).unwrap()
+}
+
+/// The results of `query` as JSON, one per line.
+fn json(query: &str) -> String {
+    run(query)
+        .iter()
+        .map(|val| serde_json::to_string(val).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn paths_pick_values() {
+    assert_eq!(json(".name"), r#""app""#);
+    assert_eq!(json(".servers[1].port"), "8080");
+    assert_eq!(json(".servers[-1].host"), r#""b.example""#);
+    assert_eq!(json(r#"."tags"[1:]"#), r#"["api","web"]"#);
+    assert_eq!(json(".servers[].host"), "\"a.example\"\n\"b.example\"");
+    assert_eq!(json(".missing.key"), "null");
+    assert_eq!(json(".dependencies | keys"), r#"["clap","serde"]"#);
+    assert_eq!(
+        json("[.. | numbers]"),
+        "[80,8080]",
+        "`..` finds values at any depth"
+    );
+    assert!(Query::parse(".name[0]").unwrap().run(&run(".")[0]).is_err());
+    assert_eq!(json(".name[0]?"), "");
+}
+
+#[test]
+fn filters_and_functions_transform_values() {
+    assert_eq!(
+        json(".servers[] | select(.port > 1024) | .host"),
+        r#""b.example""#
+    );
+    assert_eq!(json(".servers | map(.port * 2 + 1)"), "[161,16161]");
+    assert_eq!(json(".tags | unique | join(\", \")"), r#""api, web""#);
+    assert_eq!(
+        json(".servers | sort_by(-.port) | map(.host | ascii_upcase)"),
+        r#"["B.EXAMPLE","A.EXAMPLE"]"#
+    );
+    assert_eq!(
+        json(
+            ".dependencies | to_entries | map(select(.value | type == \"string\")) | from_entries"
+        ),
+        r#"{"serde":"1.0"}"#
+    );
+    assert_eq!(
+        json("{name, ports: [.servers[].port], count: (.servers | length)}"),
+        r#"{"name":"app","ports":[80,8080],"count":2}"#
+    );
+    assert_eq!(
+        json("7 / 2, 8 / 2, 7 % 2, (.x // \"none\")"),
+        "3.5\n4\n1\n\"none\""
+    );
+    assert_eq!(
+        json(r#""a,b" / "," | map(ltrimstr("a")), ("x" * 3), ("abc" | startswith("ab"))"#),
+        "[\"\",\"b\"]\n\"xxx\"\ntrue"
+    );
+    assert_eq!(
+        json("if .servers[0].port == 80 then \"http\" elif false then 1 else \"https\" end"),
+        r#""http""#
+    );
+}
+
+#[test]
+fn assignments_change_values_at_paths() {
+    assert_eq!(
+        json(".servers[].port |= . + 1 | [.servers[].port]"),
+        "[81,8081]"
+    );
+    assert_eq!(
+        json(".servers[0].tls.enabled = true | .servers[0].tls"),
+        r#"{"enabled":true}"#
+    );
+    assert_eq!(json(".tags += [\"new\"] | .tags | length"), "4");
+    assert_eq!(
+        json("del(.servers[] | select(.port == 80)) | [.servers[].host]"),
+        r#"["b.example"]"#
+    );
+    assert_eq!(json(".tags | del(.[0, 2])"), r#"["api"]"#);
+    assert_eq!(json("[paths] | length"), "18");
+    assert_eq!(json("path(.servers[0].host)"), r#"["servers",0,"host"]"#);
+    let err = Query::parse("(1 + 1) = 3")
+        .unwrap()
+        .run(&Value::Null)
+        .unwrap_err();
+    assert!(matches!(err, Error::QueryFailed(_)), "{err}");
+}
+
+#[test]
+fn results_are_made_as_they_are_needed() {
+    assert_eq!(json("first(range(100000000000))"), "0");
+    assert_eq!(json("[limit(3; range(1e18))]"), "[0,1,2]");
+    assert_eq!(json("first(range(1e18) | select(. > 5))"), "6");
+    assert_eq!(json("first(1 | recurse(. * 2) | select(. > 1000))"), "1024");
+    assert_eq!(json("last(range(3)), [range(2; 4)]"), "2\n[2,3]");
+    assert_eq!(
+        json(r#"[(1, error("stop"), 2)?]"#),
+        "[1]",
+        "`?` keeps the results before the error"
+    );
+}
+
+#[test]
+fn huge_arrays_and_strings_are_errors() {
+    for (query, message) in [
+        (".a[100000000000000000] = 1", "Array index too large"),
+        (".a[536870912] = 1", "Array index too large"),
+        (r#""x" * 1e12"#, "Repeat string result too long"),
+    ] {
+        let err = Query::parse(query).unwrap().run(&Value::Null).unwrap_err();
+        assert!(matches!(err, Error::QueryFailed(_)), "{query}: {err}");
+        assert_eq!(err.to_string(), message, "{query}");
+    }
+    assert_eq!(json(".a[3] = 1 | .a"), "[null,null,null,1]");
+    assert_eq!(json(r#""ab" * 3"#), r#""ababab""#);
+}
+
+#[test]
+fn recursive_descent_goes_depth_first() {
+    assert_eq!(
+        json("[.servers | .. | scalars]"),
+        r#"["a.example",80,"b.example",8080]"#
+    );
+    assert_eq!(json("[limit(2; ..)] | .[1]"), r#""app""#);
+    assert_eq!(json("[recurse] | length"), json("[..] | length"));
+}
+
+#[test]
+fn invalid_queries_report_the_column() {
+    for (text, message) in [
+        (
+            ".a[",
+            "Invalid query at column 4: unexpected end of the query",
+        ),
+        (
+            ".a | foo(1)",
+            "Invalid query at column 6: unknown function foo/1",
+        ),
+        (
+            "(.a",
+            "Invalid query at column 4: expected `)`, found the end of the query",
+        ),
+        (
+            "$x",
+            "Invalid query at column 1: variables are not supported",
+        ),
+        (
+            "def inc: . + 1; inc",
+            "Invalid query at column 1: `def` is not supported",
+        ),
+        (
+            "{a: 1 b}",
+            "Invalid query at column 7: expected `,`, found `b`",
+        ),
+        (
+            r#""\ud800\u0041""#,
+            "Invalid query at column 1: invalid unicode escape",
+        ),
+    ] {
+        let err = Query::parse(text).unwrap_err();
+        assert_eq!(err.to_string(), message, "{text}");
+    }
+}
+
+#[test]
+fn cli_queries_every_format() {
+    let inputs = [
+        ("toml", "[dependencies]\nserde = \"1\"\nclap = \"4\"\n"),
+        ("json", r#"{"dependencies": {"serde": "1", "clap": "4"}}"#),
+        ("json5", "{dependencies: {serde: '1', clap: '4'}}"),
+        ("yaml", "dependencies:\n  serde: \"1\"\n  clap: \"4\"\n"),
+        ("ini", "[dependencies]\nserde = 1\nclap = 4\n"),
+    ];
+    for (format, input) in inputs {
+        let args = [
+            "query",
+            ".dependencies | keys",
+            "-",
+            "--from",
+            format,
+            "-t",
+            "yaml",
+        ];
+        assert_eq!(
+            stdout(truns(&args, input)),
+            "---\n- clap\n- serde\n",
+            "{format}"
+        );
+    }
+}
+
+#[test]
+fn cli_prints_each_result() {
+    let query = |query, args: &[&str]| {
+        let args = [&["query", query, "-", "--from", "toml"], args].concat();
+        stdout(truns(&args, CONFIG))
+    };
+    assert_eq!(query(".servers[].host", &[]), "a.example\nb.example\n");
+    assert_eq!(query(".servers[0].port, .name", &[]), "80\napp\n");
+    assert_eq!(
+        query(".servers[1]", &[]),
+        "host = \"b.example\"\nport = 8080\n"
+    );
+    assert_eq!(
+        query(".servers[1]", &["-t", "json"]),
+        "{\n  \"host\": \"b.example\",\n  \"port\": 8080\n}\n"
+    );
+    assert_eq!(query("[.servers[].port]", &[]), "[\n  80,\n  8080\n]\n");
+
+    let yaml = "name: a\n---\nname: b\n";
+    let out = stdout(truns(&["query", ".name", "-", "--from", "yaml"], yaml));
+    assert_eq!(out, "a\nb\n");
+
+    let out = truns(
+        &["query", ".name | error(\"bad\")", "-", "--from", "yaml"],
+        yaml,
+    );
+    assert!(!out.status.success());
+    assert_eq!(String::from_utf8(out.stderr).unwrap(), "error: bad\n");
+}
+
+#[test]
+fn cli_refuses_deeply_nested_queries() {
+    let deep = |n| format!("{}1{}", "[".repeat(n), "]".repeat(n));
+    let out = truns(&["query", &deep(20_000), "-", "--from", "json"], "{}");
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(
+        stderr.contains("Invalid query at column 129: the query nests more than 128 levels deep"),
+        "{stderr}"
+    );
+
+    let args = ["query", &deep(100), "-", "--from", "json", "-t", "json"];
+    assert!(stdout(truns(&args, "{}")).contains('1'));
+}
@@ -0,0 +1,123 @@
+//! Formats added through `truns::registry`, used like the built-in ones.
+
+use std::path::Path;
+use std::process::Command;
+
+use truns::registry::{self, BoxError, FileFormat};
+use truns::{Error, Format, StyleOptions, Table, Value};
+
+/// Flat `key: value` lines, with every value a string.
+struct Colon;
+
+impl FileFormat for Colon {
+    fn name(&self) -> &'static str {
+        "colon"
+    }
+    fn extensions(&self) -> &'static [&'static str] {
+        &["colon", "cln"]
+    }
+    fn parse(&self, input: &[u8]) -> Result<Value, BoxError> {
+        let mut table = Table::default();
+        for line in std::str::from_utf8(input)?.lines() {
+            let (key, val) = line.split_once(": ").ok_or("expected `key: value`")?;
+            table
+                .items
+                .insert(key.to_owned(), Value::String(val.to_owned()));
+        }
+        Ok(Value::Table(table))
+    }
+    fn emit(&self, value: &Value, _style: &StyleOptions) -> Result<Vec<u8>, BoxError> {
+        let Value::Table(table) = value else {
+            return Err("not a table".into());
+        };
+        let mut out = String::new();
+        for (key, val) in &table.items {
+            match val {
+                Value::String(s) => out += &format!("{key}: {s}\n"),
+                Value::Table(_) | Value::Array(_) => return Err(format!("{key} is nested").into()),
+                val => out += &format!("{key}: {val}\n"),
+            }
+        }
+        Ok(out.into_bytes())
+    }
+}
+
+fn colon() -> Format {
+    registry::register(Colon)
+}
+
+#[test]
+fn registered_formats_convert_both_ways() {
+    let format = colon();
+    assert_eq!(format, Format::Custom("colon"));
+    let table = Table::parse("name: truns\nport: 8080\n", format).unwrap();
+    let toml = table
+        .to_string(Format::Toml, &StyleOptions::default())
+        .unwrap();
+    assert_eq!(toml, "name = \"truns\"\nport = \"8080\"\n");
+
+    let table = Table::parse("name = \"truns\"\nport = 8080\n", Format::Toml).unwrap();
+    let text = table.to_string(format, &StyleOptions::default()).unwrap();
+    assert_eq!(text, "name: truns\nport: 8080\n");
+}
+
+#[test]
+fn registered_formats_are_found_by_name_and_extension() {
+    let format = colon();
+    assert_eq!("COLON".parse::<Format>(), Ok(format));
+    assert_eq!("cln".parse::<Format>(), Ok(format));
+    assert_eq!(Format::from_extension(Path::new("app.cln")), Some(format));
+    assert_eq!(format.extensions(), ["colon", "cln"]);
+    assert!(registry::formats().contains(&format));
+    assert!(registry::formats().contains(&Format::Toml));
+}
+
+#[test]
+fn errors_of_registered_formats_are_reported() {
+    let format = colon();
+    let err = Table::parse("no separator\n", format).unwrap_err();
+    assert!(err.to_string().contains("expected `key: value`"), "{err}");
+
+    let table = Table::parse("[server]\nport = 8080\n", Format::Toml).unwrap();
+    let err = table
+        .to_string(format, &StyleOptions::default())
+        .unwrap_err();
+    assert!(matches!(err, Error::Serialize { .. }), "{err}");
+}
+
+#[test]
+#[should_panic(expected = "taken by a built-in format")]
+fn built_in_names_cannot_be_registered() {
+    struct Fake;
+    impl FileFormat for Fake {
+        fn name(&self) -> &'static str {
+            "json"
+        }
+        fn parse(&self, _input: &[u8]) -> Result<Value, BoxError> {
+            unimplemented!()
+        }
+        fn emit(&self, _value: &Value, _style: &StyleOptions) -> Result<Vec<u8>, BoxError> {
+            unimplemented!()
+        }
+    }
+    registry::register(Fake);
+}
+
+#[test]
+fn cli_lists_the_built_in_formats() {
+    let out = Command::new(env!("CARGO_BIN_EXE_truns"))
+        .arg("formats")
+        .output()
+        .expect("failed to run truns");
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let names: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    assert_eq!(
+        names,
+        ["json", "jsonl", "jsonc", "json5", "toml", "yaml", "ini"]
+    );
+    assert!(stdout.contains(".yaml .yml"), "{stdout}");
+}
@@ -0,0 +1,225 @@
+//! Round trips of the documents in `tests/corpus` through every format,
+//! using `truns check-roundtrip`.
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::corpus;
+
+fn check_roundtrip(name: &str, via: &str, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_truns"))
+        .arg("check-roundtrip")
+        .arg(corpus(name))
+        .args(["--via", via])
+        .args(extra)
+        .output()
+        .expect("failed to run truns")
+}
+
+/// Asserts the round trip lists exactly `expected` as differences.
+fn assert_differences(name: &str, via: &str, extra: &[&str], expected: &[&str]) {
+    let out = check_roundtrip(name, via, extra);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        expected,
+        "{name} via {via}: {stderr}"
+    );
+    assert_eq!(
+        out.status.success(),
+        expected.is_empty(),
+        "{name} via {via}"
+    );
+}
+
+/// Asserts the round trip fails before comparing, with `message` in the error.
+fn assert_error(name: &str, via: &str, extra: &[&str], message: &str) {
+    let out = check_roundtrip(name, via, extra);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(!out.status.success(), "{name} via {via} should fail");
+    assert!(stderr.contains(message), "{name} via {via}: {stderr}");
+}
+
+#[test]
+fn exact_round_trips() {
+    let cases: &[(&str, &[&str])] = &[
+        (
+            "scalars.json",
+            &["json", "jsonl", "jsonc", "json5", "toml", "yaml"],
+        ),
+        ("nested.json", &["json", "toml", "yaml"]),
+        ("nulls.json", &["json", "yaml"]),
+        ("config.toml", &["toml"]),
+        ("commented.toml", &["toml", "yaml"]),
+        ("anchors.yaml", &["toml", "yaml"]),
+        ("keys.yaml", &["json", "toml", "yaml", "ini"]),
+        ("settings.ini", &["json", "toml", "yaml", "ini"]),
+        ("stream.yaml", &["json", "toml", "yaml"]),
+        ("editor.json5", &["json5", "toml", "yaml"]),
+        (
+            "settings.jsonc",
+            &["json", "jsonc", "json5", "toml", "yaml"],
+        ),
+    ];
+    for (name, formats) in cases {
+        for via in *formats {
+            assert_differences(name, via, &[], &[]);
+        }
+    }
+}
+
+#[test]
+fn datetimes_become_strings_in_json() {
+    assert_differences(
+        "config.toml",
+        "json",
+        &["--non-finite", "string"],
+        &[
+            r#"positive_infinity: inf became "Infinity""#,
+            r#"negative_infinity: -inf became "-Infinity""#,
+            r#"not_a_number: NaN became "NaN""#,
+            r#"owner.dob: 1979-05-27T07:32:00-08:00 became "1979-05-27T07:32:00-08:00""#,
+            r#"owner.local: 1979-05-27T07:32:00 became "1979-05-27T07:32:00""#,
+            r#"owner.day: 1979-05-27 became "1979-05-27""#,
+            r#"owner.lunch: 12:30:00 became "12:30:00""#,
+        ],
+    );
+}
+
+#[test]
+fn local_times_become_strings_in_yaml() {
+    assert_differences(
+        "config.toml",
+        "yaml",
+        &[],
+        &[r#"owner.lunch: 12:30:00 became "12:30:00""#],
+    );
+}
+
+#[test]
+fn non_finite_floats_become_null_in_json() {
+    assert_differences(
+        "anchors.yaml",
+        "json",
+        &["--non-finite", "null"],
+        &[
+            "special_floats[0]: inf became null",
+            "special_floats[1]: -inf became null",
+            "special_floats[2]: NaN became null",
+            r#"timestamp: 2001-12-14T21:59:43.1-05:00 became "2001-12-14T21:59:43.1-05:00""#,
+            r#"date: 2002-12-14 became "2002-12-14""#,
+        ],
+    );
+}
+
+#[test]
+fn dropped_nulls_go_missing_in_toml() {
+    assert_differences(
+        "nulls.json",
+        "toml",
+        &["--null", "drop"],
+        &[
+            "main: null went missing",
+            "deps.left-pad: null went missing",
+            "list[1]: null became 3",
+            "list[2]: 3 went missing",
+        ],
+    );
+}
+
+#[test]
+fn toml_reports_values_moved_ahead_of_tables() {
+    let out = Command::new(env!("CARGO_BIN_EXE_truns"))
+        .arg("convert")
+        .arg(corpus("nested.json"))
+        .args(["-t", "toml", "--strict"])
+        .output()
+        .expect("failed to run truns");
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "\
+error: Converting to toml would change 2 values
+  matrix: moved ahead of the tables before it
+  mixed: moved ahead of the tables before it
+"
+    );
+}
+
+#[test]
+fn ini_loses_types() {
+    assert_differences(
+        "stream.yaml",
+        "ini",
+        &[],
+        &[r#"[1].spec.replicas: 3 became "3""#],
+    );
+    assert_differences("stream.yaml", "ini", &["--ini-infer-types"], &[]);
+}
+
+#[test]
+fn ini_reports_keys_and_values_it_would_change() {
+    assert_error(
+        "ini_unsafe.json",
+        "ini",
+        &[],
+        r#"server["a=b"]: ini can not represent a key with `=` or `:` in it"#,
+    );
+    assert_differences(
+        "ini_unsafe.json",
+        "ini",
+        &["--ini-policy", "skip"],
+        &[
+            r#"server.host: " padded " became "padded""#,
+            r#"server.motd: "\"quoted\"" became "quoted""#,
+            r#"server["a=b"]: "c" went missing"#,
+        ],
+    );
+
+    let out = Command::new(env!("CARGO_BIN_EXE_truns"))
+        .arg("convert")
+        .arg(corpus("ini_unsafe.json"))
+        .args(["-t", "ini", "--strict", "--ini-policy", "skip"])
+        .output()
+        .expect("failed to run truns");
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "\
+error: Converting to ini would change 3 values
+  server.host: whitespace at the ends trimmed when read back
+  server.motd: leading quote read back as quoting
+  server[\"a=b\"]: a key with `=` or `:` in it, left out with its value
+"
+    );
+}
+
+#[test]
+fn unrepresentable_values_are_errors() {
+    assert_error(
+        "nulls.json",
+        "toml",
+        &[],
+        "main: toml can not represent null",
+    );
+    assert_error(
+        "nested.json",
+        "ini",
+        &[],
+        "servers: ini can not represent arrays",
+    );
+    assert_error(
+        "config.toml",
+        "json",
+        &[],
+        "positive_infinity: json can not represent the non-finite float inf",
+    );
+    assert_error(
+        "editor.json5",
+        "jsonc",
+        &[],
+        "maxWidth: jsonc can not represent the non-finite float inf",
+    );
+}
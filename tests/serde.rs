@@ -0,0 +1,160 @@
+//! `Value` and `Table` as serde types, and typed configs through
+//! `to_value` and `from_value`.
+
+use serde::{Deserialize, Serialize};
+use truns::options::NullPolicy;
+use truns::value::IniOptions;
+use truns::{from_value, to_value, Datetime, Format, Options, StyleOptions, Table, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    port: u16,
+    debug: Option<bool>,
+    level: Level,
+    released: Datetime,
+    servers: Vec<Server>,
+    /// Anything else, kept as it was read.
+    extra: Value,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Info,
+    Warn,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Server {
+    Local(u16),
+    Remote { host: String, port: u16 },
+}
+
+const TOML: &str = r#"name = "app"
+port = 8080
+level = "warn"
+released = 2024-05-27T07:32:00Z
+extra = { retries = -1, ratio = 0.5 }
+
+[[servers]]
+Local = 80
+
+[[servers]]
+Remote = { host = "alpha", port = 443 }
+"#;
+
+fn config() -> Config {
+    Config {
+        name: "app".to_owned(),
+        port: 8080,
+        debug: None,
+        level: Level::Warn,
+        released: "2024-05-27T07:32:00Z".parse().unwrap(),
+        servers: vec![
+            Server::Local(80),
+            Server::Remote {
+                host: "alpha".to_owned(),
+                port: 443,
+            },
+        ],
+        extra: Value::Table(Table::new([
+            ("retries".to_owned(), Value::Int(-1)),
+            ("ratio".to_owned(), Value::Float(0.5)),
+        ])),
+    }
+}
+
+#[test]
+fn tables_deserialize_into_typed_configs() {
+    let table = Table::parse(TOML, Format::Toml).unwrap();
+    assert_eq!(from_value::<Config>(table).unwrap(), config());
+}
+
+#[test]
+fn typed_configs_serialize_into_tables() {
+    let Value::Table(table) = to_value(&config()).unwrap() else {
+        panic!("structs serialize as tables");
+    };
+    // `debug` is null, which TOML has no place for.
+    let options = Options {
+        null: NullPolicy::DropKeys,
+        ..Options::default()
+    };
+    let style = StyleOptions {
+        toml_inline_tables: 2,
+        line_width: Some(80),
+        ..StyleOptions::default()
+    };
+    let (toml, _) = table
+        .to_string_with(Format::Toml, &options, &IniOptions::default(), &style)
+        .unwrap();
+    assert_eq!(toml, TOML);
+}
+
+#[test]
+fn values_survive_serde() {
+    let table = Table::parse(TOML, Format::Toml).unwrap();
+    let value = Value::Table(table.clone());
+    assert_eq!(to_value(&value).unwrap(), value);
+    assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+    assert_eq!(from_value::<Table>(table.clone()).unwrap(), table);
+}
+
+#[test]
+fn datetimes_serialize_as_datetimes() {
+    let table = Table::parse("released = 2024-05-27T07:32:00Z\n", Format::Toml).unwrap();
+    assert_eq!(
+        toml::to_string(&table).unwrap(),
+        "released = 2024-05-27T07:32:00Z\n"
+    );
+    let json = serde_json::to_string(&table).unwrap();
+    assert_eq!(serde_json::from_str::<Table>(&json).unwrap(), table);
+}
+
+#[test]
+fn datetimes_read_as_strings_when_asked() {
+    #[derive(Deserialize)]
+    struct Release {
+        released: String,
+    }
+    let table = Table::parse(TOML, Format::Toml).unwrap();
+    let release: Release = from_value(table).unwrap();
+    assert_eq!(release.released, "2024-05-27T07:32:00Z");
+}
+
+#[test]
+fn mismatched_types_are_errors() {
+    #[derive(Debug, Deserialize)]
+    struct Port {
+        #[allow(dead_code)]
+        port: u16,
+    }
+    let table = Table::parse("port = \"http\"", Format::Toml).unwrap();
+    let err = from_value::<Port>(table).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"invalid type: string "http", expected u16"#
+    );
+    let table = Table::parse("port = 70000", Format::Toml).unwrap();
+    let err = from_value::<Port>(table).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value: integer `70000`, expected u16"
+    );
+}
+
+#[test]
+fn errors_keep_their_cause() {
+    use std::error::Error as _;
+
+    let err = to_value(&u128::MAX).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("integer {} is out of range", u128::MAX)
+    );
+    assert!(err.source().is_some());
+
+    let err = from_value::<Level>(Value::Bool(true)).unwrap_err();
+    assert!(err.source().is_none(), "serde only gives a message");
+}
@@ -0,0 +1,221 @@
+//! Output layout with the style options of `truns convert`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn convert(input: &str, from: &str, to: &str, style: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_truns"))
+        .args(["convert", "--comments", "-f", from, "-t", to])
+        .args(style)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run truns");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{from} to {to} failed");
+    String::from_utf8(out.stdout).unwrap()
+}
+
+const JSON: &str = r#"{
+  "name": "app",
+  "point": {"x": 1, "y": 2},
+  "tags": ["web", "it's"],
+  "servers": [{"host": "alpha", "ports": [80, 443]}]
+}"#;
+
+#[test]
+fn compact_json_has_no_whitespace() {
+    assert_eq!(
+        convert(
+            JSON,
+            "json",
+            "json",
+            &["--compact", "--no-trailing-newline"]
+        ),
+        r#"{"name":"app","point":{"x":1,"y":2},"tags":["web","it's"],"servers":[{"host":"alpha","ports":[80,443]}]}"#
+    );
+}
+
+#[test]
+fn collections_that_fit_go_on_one_line() {
+    let expected = r#"{
+    "name": "app",
+    "point": {"x": 1, "y": 2},
+    "tags": ["web", "it's"],
+    "servers": [
+        {"host": "alpha", "ports": [80, 443]}
+    ]
+}
+"#;
+    let style = ["--indent", "4", "--line-width", "48"];
+    assert_eq!(convert(JSON, "json", "json", &style), expected);
+}
+
+#[test]
+fn yaml_flow_and_quotes() {
+    let expected = "\
+---
+{
+  'name': 'app',
+  'point': {'x': 1, 'y': 2},
+  'tags': ['web', 'it''s'],
+  'servers': [{'host': 'alpha', 'ports': [80, 443]}]
+}
+";
+    let style = [
+        "--yaml-style",
+        "flow",
+        "--line-width",
+        "60",
+        "--key-quotes",
+        "always",
+        "--string-quotes",
+        "single",
+    ];
+    assert_eq!(convert(JSON, "json", "yaml", &style), expected);
+}
+
+#[test]
+fn yaml_quotes_strings_that_read_back_as_other_types() {
+    let json = r#"{"s": ["0o12", "0x1f", "+1", "1e3", ".inf", ".NaN", "~", "Null", "TRUE", "yes", "2024-05-27", "2024-05-27T07:32:00Z", ".hidden", "y", "a b"]}"#;
+    let yaml = convert(json, "json", "yaml", &[]);
+    let expected = "\
+---
+s:
+  - \"0o12\"
+  - \"0x1f\"
+  - \"+1\"
+  - \"1e3\"
+  - \".inf\"
+  - \".NaN\"
+  - \"~\"
+  - \"Null\"
+  - \"TRUE\"
+  - \"yes\"
+  - \"2024-05-27\"
+  - \"2024-05-27T07:32:00Z\"
+  - .hidden
+  - y
+  - a b
+";
+    assert_eq!(yaml, expected);
+    let compact = ["--compact", "--no-trailing-newline"];
+    assert_eq!(
+        convert(&yaml, "yaml", "json", &compact),
+        convert(json, "json", "json", &compact)
+    );
+}
+
+#[test]
+fn yaml_nests_even_without_indent() {
+    let json = r#"{"nested":{"a":[1,2]},"l":[[1],{"x":1}]}"#;
+    let yaml = convert(json, "json", "yaml", &["--indent", "0"]);
+    assert_eq!(yaml, convert(json, "json", "yaml", &["--indent", "1"]));
+    let compact = ["--compact", "--no-trailing-newline"];
+    assert_eq!(convert(&yaml, "yaml", "json", &compact), json);
+}
+
+#[test]
+fn small_toml_tables_are_inline() {
+    let expected = "\
+name = \"app\"
+point = { x = 1, y = 2 }
+tags = [\"web\", \"it's\"]
+
+[[servers]]
+host = \"alpha\"
+ports = [80, 443]
+";
+    let style = ["--toml-inline-tables", "2", "--line-width", "30"];
+    assert_eq!(convert(JSON, "json", "toml", &style), expected);
+}
+
+#[test]
+fn comments_keep_collections_apart() {
+    let toml = "\
+ports = [
+  8000, # primary
+  8001,
+]
+";
+    // A comment can not share a line with other values, so the array stays
+    // on several lines however wide they may be.
+    assert_eq!(
+        convert(
+            toml,
+            "toml",
+            "toml",
+            &["--line-width", "80", "--indent", "2"]
+        ),
+        toml
+    );
+}
+
+const NESTED: &str = r#"{
+  "servers": [
+    {"name": "alpha", "tls": {"cert": "a.pem"}, "ports": [{"port": 80}, {"port": 443}], "region": "eu"}
+  ],
+  "grid": [[{"x": 1}], [{"x": 2}]],
+  "deep": {"a": {"b": {"c": 1}}},
+  "title": "web"
+}"#;
+
+#[test]
+fn toml_tables_get_headers_after_the_values() {
+    let expected = "\
+grid = [
+    [
+        { x = 1 },
+    ],
+    [
+        { x = 2 },
+    ],
+]
+title = \"web\"
+
+[[servers]]
+name = \"alpha\"
+region = \"eu\"
+
+[servers.tls]
+cert = \"a.pem\"
+
+[[servers.ports]]
+port = 80
+
+[[servers.ports]]
+port = 443
+
+[deep.a.b]
+c = 1
+";
+    assert_eq!(convert(NESTED, "json", "toml", &[]), expected);
+}
+
+#[test]
+fn toml_dotted_keys_collapse_single_key_chains() {
+    let expected = "\
+grid = [[{ x = 1 }], [{ x = 2 }]]
+deep.a.b.c = 1
+title = \"web\"
+
+[[servers]]
+name = \"alpha\"
+tls.cert = \"a.pem\"
+region = \"eu\"
+
+[[servers.ports]]
+port = 80
+
+[[servers.ports]]
+port = 443
+";
+    let style = ["--toml-dotted-keys", "--line-width", "80"];
+    assert_eq!(convert(NESTED, "json", "toml", &style), expected);
+}
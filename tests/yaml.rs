@@ -0,0 +1,104 @@
+//! Reading YAML: aliases expanded under a size limit, and `<<` merge keys,
+//! with `truns::parse` and with `truns convert`.
+
+mod common;
+
+use truns::detect::Parsed;
+use truns::options::LoadOptions;
+use truns::{parse, Error, Format, Table};
+
+use common::truns;
+
+const ALIASES: &str = "a: &x [1, 2, 3]\nb: *x\nc: *x\n";
+
+/// The number of documents in `text` loaded with `alias_limit`, or the error.
+fn load(text: &str, alias_limit: usize) -> Result<usize, String> {
+    match parse::parse(text, Format::Yaml, &LoadOptions { alias_limit }) {
+        Ok(Parsed::Yaml(docs)) => Ok(docs.len()),
+        Ok(other) => panic!("expected YAML, got {other:?}"),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn json(text: &str) -> String {
+    let table = Table::parse(text, Format::Yaml).unwrap();
+    serde_json::to_string(&table).unwrap()
+}
+
+#[test]
+fn aliases_expand_up_to_the_limit() {
+    // Each alias of the three element array adds four nodes.
+    assert_eq!(load(ALIASES, 8), Ok(1));
+    assert_eq!(
+        load(ALIASES, 7),
+        Err("Failed to parse yaml: Aliases expand to more than 7 nodes".to_owned())
+    );
+    assert_eq!(
+        load("a: &x [1, 2, 3]\nb: *x\n---\nc: &y [1, 2, 3]\nd: *y\n", 7),
+        Err("Failed to parse yaml: Aliases expand to more than 7 nodes".to_owned()),
+        "the limit is for the whole stream"
+    );
+}
+
+#[test]
+fn billion_laughs_stop_at_the_default_limit() {
+    let mut text = "a0: &a0 [lol, lol, lol, lol, lol, lol, lol, lol, lol]\n".to_owned();
+    for i in 1..10 {
+        let prev = format!("*a{}", i - 1);
+        text += &format!("a{i}: &a{i} [{}]\n", vec![prev; 9].join(", "));
+    }
+    let err = load(&text, LoadOptions::default().alias_limit).unwrap_err();
+    assert_eq!(
+        err,
+        "Failed to parse yaml: Aliases expand to more than 100000 nodes"
+    );
+}
+
+#[test]
+fn merge_keys_never_override_keys_written_out() {
+    let base = "base: &b {a: 1, b: 2}\nother: &o {b: 3, c: 4}\n";
+    assert_eq!(
+        json(&format!("{base}m:\n  c: 5\n  <<: [*b, *o]\n  d: 6\n")),
+        r#"{"base":{"a":1,"b":2},"other":{"b":3,"c":4},"m":{"a":1,"b":2,"c":5,"d":6}}"#,
+        "merged keys come first, the first merged mapping wins"
+    );
+    assert_eq!(
+        json(&format!("{base}m:\n  <<: *o\n  b: 5\n")),
+        r#"{"base":{"a":1,"b":2},"other":{"b":3,"c":4},"m":{"c":4,"b":5}}"#,
+        "keys after the merge key still win"
+    );
+    assert_eq!(
+        json(&format!("{base}m:\n  <<: [*o, *b]\n")),
+        r#"{"base":{"a":1,"b":2},"other":{"b":3,"c":4},"m":{"b":3,"c":4,"a":1}}"#
+    );
+    assert_eq!(
+        json("m: {'<<': {a: 1}}\n"),
+        r#"{"m":{"<<":{"a":1}}}"#,
+        "a quoted `<<` is a plain key"
+    );
+}
+
+#[test]
+fn merge_keys_need_mappings() {
+    for text in ["m:\n  <<: 1\n", "m:\n  <<: [{a: 1}, 2]\n"] {
+        let Err(Error::Parse(e)) = Table::parse(text, Format::Yaml) else {
+            panic!("{text} should not parse");
+        };
+        assert_eq!(e.message, "Merge key needs a mapping or a list of mappings");
+        assert_eq!(e.location.map(|loc| loc.line), Some(2), "{text}");
+    }
+}
+
+#[test]
+fn cli_takes_the_alias_limit() {
+    let args = ["convert", "-f", "yaml", "-t", "json"];
+    let out = truns(&[&args[..], &["--yaml-alias-limit", "7"]].concat(), ALIASES);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: Failed to parse yaml: Aliases expand to more than 7 nodes\n"
+    );
+
+    let out = truns(&[&args[..], &["--yaml-alias-limit", "8"]].concat(), ALIASES);
+    assert!(out.status.success());
+}